@@ -0,0 +1,43 @@
+use rcommon::follow::{self, Follow, Followed};
+use rcommon::Reporter;
+use std::fs::File;
+use std::io::{self, BufRead, Write};
+
+/**
+ * 各ファイルの中身を出力したあと、追記されたバイト列を出力し続ける
+ *
+ * ファイル名で監視するので、ローテーションで別のファイルに置き換わった場合は開き直す。
+ * 標準入力("-")は最後まで読んだら監視しない
+ */
+pub fn follow<W: Write + ?Sized>(
+    files: &[String],
+    retry: bool,
+    stdin: &mut dyn BufRead,
+    out: &mut W,
+    reporter: &mut Reporter,
+) -> io::Result<()> {
+    let mut followed = vec![];
+    for filename in files {
+        if filename == "-" {
+            io::copy(stdin, out)?;
+            continue;
+        }
+
+        let file = File::open(filename).and_then(|mut file| {
+            io::copy(&mut file, out)?;
+            Ok(file)
+        });
+        match file {
+            Ok(file) => followed.push(Followed::new(filename, file, Follow::Name)?),
+            Err(err) => {
+                reporter.error(filename, &err);
+                if retry {
+                    followed.push(Followed::failed(filename, &err, Follow::Name));
+                }
+            }
+        }
+    }
+    out.flush()?;
+
+    follow::follow(followed, retry, false, None, out, reporter)
+}
@@ -1,11 +1,18 @@
-use std::error::Error;
-use clap::{App, Arg};
-use std::io::{self, BufRead, BufReader};
-use std::fs::File;
+use clap::Parser;
+use rcommon::cli::GenerateArgs;
+use rcommon::{Input, MyResult, Reporter, BUF_SIZE};
+use std::ffi::OsString;
+use std::io::{self, BufRead, BufWriter, Read, Write};
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
+use xz2::read::XzDecoder;
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+mod follow;
 
 /**
  * deriveマクロで、Debugトレイトを追加して構造体を表示できるようにする
- * 
+ *
  * Config構造体
  */
 #[derive(Debug)]
@@ -13,52 +20,468 @@ pub struct Config {
     files: Vec<String>,
     number_lines: bool, // 行番号を表示するかどうか
     number_nonblank_lines: bool, // 空行以外の行番号を表示するかどうか
+    show_ends: bool, // 行末に $ を表示するかどうか
+    show_tabs: bool, // タブを ^I で表示するかどうか
+    show_nonprinting: bool, // 制御文字などを ^X, M- 表記で表示するかどうか
+    squeeze_blank: bool, // 連続する空行を1行にまとめるかどうか
+    decompress: bool, // 圧縮された入力を展開するかどうか
+    follow: bool, // 最後まで出力したあとも追記を出力し続けるかどうか
+    retry: bool, // 開けないファイルを開けるようになるまで試し続けるかどうか
 }
 
-type MyResult<T> = Result<T, Box<dyn Error>>;
+impl Config {
+    /**
+     * 指定したファイルを変換せずに出力する設定を作る
+     *
+     * 各オプションはメソッドチェーンで指定する
+     */
+    pub fn new(files: Vec<String>) -> Config {
+        Config {
+            files,
+            number_lines: false,
+            number_nonblank_lines: false,
+            show_ends: false,
+            show_tabs: false,
+            show_nonprinting: false,
+            squeeze_blank: false,
+            decompress: false,
+            follow: false,
+            retry: false,
+        }
+    }
+
+    pub fn number_lines(mut self, number_lines: bool) -> Config {
+        self.number_lines = number_lines;
+        self
+    }
+
+    pub fn number_nonblank_lines(mut self, number_nonblank_lines: bool) -> Config {
+        self.number_nonblank_lines = number_nonblank_lines;
+        self
+    }
+
+    pub fn show_ends(mut self, show_ends: bool) -> Config {
+        self.show_ends = show_ends;
+        self
+    }
+
+    pub fn show_tabs(mut self, show_tabs: bool) -> Config {
+        self.show_tabs = show_tabs;
+        self
+    }
+
+    pub fn show_nonprinting(mut self, show_nonprinting: bool) -> Config {
+        self.show_nonprinting = show_nonprinting;
+        self
+    }
+
+    pub fn squeeze_blank(mut self, squeeze_blank: bool) -> Config {
+        self.squeeze_blank = squeeze_blank;
+        self
+    }
+
+    pub fn decompress(mut self, decompress: bool) -> Config {
+        self.decompress = decompress;
+        self
+    }
+
+    pub fn follow(mut self, follow: bool) -> Config {
+        self.follow = follow;
+        self
+    }
+
+    pub fn retry(mut self, retry: bool) -> Config {
+        self.retry = retry;
+        self
+    }
+
+    /**
+     * 出力時にバイト列を変換する必要があるかどうか
+     */
+    fn transforms(&self) -> bool {
+        self.number_lines
+            || self.number_nonblank_lines
+            || self.show_ends
+            || self.show_tabs
+            || self.show_nonprinting
+            || self.squeeze_blank
+    }
+}
+
+/**
+ * -z で使う展開フック
+ *
+ * 先頭のマジックバイトから圧縮形式を判定する。圧縮されていない場合は、読み込んだ先頭部分を戻してそのまま読む
+ */
+fn decode<'a>(mut reader: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+    let mut magic = Vec::with_capacity(MAGIC_LEN);
+    (&mut reader).take(MAGIC_LEN as u64).read_to_end(&mut magic)?;
+    let compression = Compression::detect(&magic);
+    let reader = io::Cursor::new(magic).chain(reader);
+
+    let compression = match compression {
+        Some(compression) => compression,
+        None => return Ok(Box::new(reader)),
+    };
+    let inner: Box<dyn Read + 'a> = match compression {
+        Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+        Compression::Bzip2 => Box::new(MultiBzDecoder::new(reader)),
+        Compression::Xz => Box::new(XzDecoder::new_multi_decoder(reader)),
+        Compression::Zstd => Box::new(ZstdDecoder::new(reader)?),
+    };
+    Ok(Box::new(Decoder { compression, inner }))
+}
+
+// 圧縮形式の判定に使う先頭バイト数 (bzip2 のヘッダーと最初のブロックのマジックが最長で10バイト)
+const MAGIC_LEN: usize = 10;
+
+// bzip2 のヘッダー ("BZh" とブロックサイズ) に続く、最初のブロックか空のストリームの終わりのマジック
+const BZIP2_BLOCK_MAGIC: [u8; 6] = [0x31, 0x41, 0x59, 0x26, 0x53, 0x59];
+const BZIP2_EOS_MAGIC: [u8; 6] = [0x17, 0x72, 0x45, 0x38, 0x50, 0x90];
+
+/**
+ * -z で展開できる圧縮形式
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Compression {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    /**
+     * 拡張子ではなく、先頭のマジックバイトで判定する
+     */
+    fn detect(magic: &[u8]) -> Option<Compression> {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            Some(Compression::Gzip)
+        } else if is_bzip2(magic) {
+            Some(Compression::Bzip2)
+        } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Compression::Xz)
+        } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Compression::Zstd)
+        } else {
+            None
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Bzip2 => "bzip2",
+            Compression::Xz => "xz",
+            Compression::Zstd => "zstd",
+        }
+    }
+}
+
+/**
+ * bzip2 のヘッダーかどうか
+ *
+ * "BZh" で始まるだけのテキストを bzip2 とみなさないように、ブロックサイズ ('1'〜'9') と
+ * それに続くブロックのマジックまで確かめる
+ */
+fn is_bzip2(magic: &[u8]) -> bool {
+    match magic {
+        [b'B', b'Z', b'h', b'1'..=b'9', block @ ..] => {
+            block == BZIP2_BLOCK_MAGIC || block == BZIP2_EOS_MAGIC
+        }
+        _ => false,
+    }
+}
+
+/**
+ * 展開中のエラーに圧縮形式の名前を付けるためのラッパー
+ */
+struct Decoder<'a> {
+    compression: Compression,
+    inner: Box<dyn Read + 'a>,
+}
+
+impl Read for Decoder<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf).map_err(|e| {
+            io::Error::new(e.kind(), format!("{}: {}", self.compression.name(), e))
+        })
+    }
+}
 
 /**
  * 引数としてConfigを受け取る
  */
 pub fn run(config: Config) -> MyResult<()> {
-    for filename in config.files {
+    let stdout = io::stdout();
+    run_with(
+        config,
+        &mut io::stdin().lock(),
+        &mut stdout.lock(),
+        &mut io::stderr(),
+    )
+}
+
+/**
+ * 標準入力・標準出力・標準エラーの代わりに、指定したリーダーとライターを使って実行する
+ *
+ * out はファイルからのコピーで copy_file_range などを使えるように、具体的な型のまま受け取る
+ */
+pub fn run_with<W: Write + ?Sized>(
+    config: Config,
+    stdin: &mut dyn BufRead,
+    out: &mut W,
+    err: &mut dyn Write,
+) -> MyResult<()> {
+    let mut reporter = Reporter::new("catr", err);
+    if config.follow {
+        follow::follow(&config.files, config.retry, stdin, out, &mut reporter)?;
+        return reporter.finish();
+    }
+
+    let mut out = BufWriter::with_capacity(BUF_SIZE, out);
+
+    for filename in &config.files {
         // 指定したファイルをオープンする
-        match open(&filename) {
-            Err(err) => eprintln!("Failed to open {}: {}", filename, err),
-            Ok(_) => println!("Opened {}", filename),
+        let res = open(filename, &mut *stdin, config.decompress).and_then(|input| {
+            if config.transforms() {
+                cat_file(&config, input.into_reader(), &mut out)
+            } else {
+                // BufWriter に残っている分を書き出してから、直接コピーする
+                out.flush()?;
+                input.copy_to(&mut **out.get_mut())?;
+                Ok(())
+            }
+        });
+        if let Err(err) = res {
+            // 標準出力と標準エラーの順序を保つため、先にフラッシュする
+            out.flush()?;
+            reporter.error(filename, err);
         }
     }
+    out.flush()?;
+    reporter.finish()
+}
+
+/**
+ * 1つの入力を行単位(バイト列)で読み込み、オプションに従って変換して出力する
+ *
+ * UTF-8として解釈しないので、不正なバイト列もそのまま扱える
+ */
+fn cat_file(
+    config: &Config,
+    mut file: impl BufRead,
+    out: &mut impl Write,
+) -> MyResult<()> {
+    let mut line = Vec::new();
+    let mut line_num = 0;
+    let mut prev_blank = false;
+
+    loop {
+        line.clear();
+        let bytes = file.read_until(b'\n', &mut line)?;
+        // EOFに達すると0byteが返される
+        if bytes == 0 {
+            break;
+        }
+
+        let blank = line == b"\n";
+        // -s: 直前の行も空行なら出力しない
+        if config.squeeze_blank && blank && prev_blank {
+            continue;
+        }
+        prev_blank = blank;
+
+        if config.number_lines || (config.number_nonblank_lines && !blank) {
+            line_num += 1;
+            write!(out, "{:>6}\t", line_num)?;
+        }
+        write_line(config, &line, out)?;
+    }
+    Ok(())
+}
+
+/**
+ * 改行を含む1行を、-E, -T, -v の指定に従って出力する
+ */
+fn write_line(config: &Config, line: &[u8], out: &mut impl Write) -> MyResult<()> {
+    let (body, newline) = match line.strip_suffix(b"\n") {
+        Some(body) => (body, true),
+        None => (line, false),
+    };
+    // GNU cat と同様に、-E では CRLF の CR を ^M として表示する
+    let (body, cr) = match body.strip_suffix(b"\r") {
+        Some(body) if config.show_ends && newline => (body, true),
+        _ => (body, false),
+    };
+
+    if !config.show_tabs && !config.show_nonprinting {
+        out.write_all(body)?;
+    } else {
+        for &byte in body {
+            write_byte(config, byte, out)?;
+        }
+    }
+
+    if cr {
+        out.write_all(b"^M")?;
+    }
+    if newline {
+        if config.show_ends {
+            out.write_all(b"$")?;
+        }
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/**
+ * 1バイトを ^X / M- 表記に変換して出力する
+ */
+fn write_byte(config: &Config, byte: u8, out: &mut impl Write) -> MyResult<()> {
+    if byte == b'\t' {
+        if config.show_tabs {
+            out.write_all(b"^I")?;
+        } else {
+            out.write_all(b"\t")?;
+        }
+        return Ok(());
+    }
+    if !config.show_nonprinting {
+        out.write_all(&[byte])?;
+        return Ok(());
+    }
+
+    // 上位ビットが立っているバイトは M- を付けて下位7ビットとして表示する
+    let low = if byte >= 128 {
+        out.write_all(b"M-")?;
+        byte - 128
+    } else {
+        byte
+    };
+    match low {
+        0..=31 => out.write_all(&[b'^', low + 64])?,
+        127 => out.write_all(b"^?")?,
+        _ => out.write_all(&[low])?,
+    }
     Ok(())
 }
 
+// コマンドライン引数の定義 (ヘルプ・補完スクリプト・manページにも使われる)
+#[derive(Debug, Parser)]
+#[command(
+    name = "catr",
+    version = "0.1.0",
+    author = "Hoge <hoge@example.com>",
+    about = "Rust cat"
+)]
+struct Args {
+    /// Input file(s)
+    #[arg(value_name = "FILE", default_value = "-")]
+    files: Vec<String>,
+
+    /// Number all output lines
+    #[arg(short = 'n', long = "number", conflicts_with = "number_nonblank")]
+    number: bool,
+
+    /// Number non-blank output lines
+    #[arg(short = 'b', long = "number-nonblank")]
+    number_nonblank: bool,
+
+    /// Equivalent to -vET
+    #[arg(short = 'A', long = "show-all")]
+    show_all: bool,
+
+    /// Equivalent to -vE
+    #[arg(short = 'e')]
+    e: bool,
+
+    /// Display $ at end of each line
+    #[arg(short = 'E', long = "show-ends")]
+    show_ends: bool,
+
+    /// Equivalent to -vT
+    #[arg(short = 't')]
+    t: bool,
+
+    /// Display TAB characters as ^I
+    #[arg(short = 'T', long = "show-tabs")]
+    show_tabs: bool,
+
+    /// Use ^ and M- notation, except for LFD and TAB
+    #[arg(short = 'v', long = "show-nonprinting")]
+    show_nonprinting: bool,
+
+    /// Decompress gzip, bzip2, xz and zstd input
+    #[arg(short = 'z', long = "decompress")]
+    decompress: bool,
+
+    /// Output appended data as the files grow
+    #[arg(
+        short = 'f',
+        long = "follow",
+        conflicts_with_all = [
+            "number",
+            "number_nonblank",
+            "show_all",
+            "e",
+            "show_ends",
+            "t",
+            "show_tabs",
+            "show_nonprinting",
+            "squeeze_blank",
+            "decompress",
+        ]
+    )]
+    follow: bool,
+
+    /// Keep trying to open a file if it is inaccessible
+    #[arg(long = "retry", requires = "follow")]
+    retry: bool,
+
+    /// Suppress repeated empty output lines
+    #[arg(short = 's', long = "squeeze-blank")]
+    squeeze_blank: bool,
+
+    #[command(flatten)]
+    generate: GenerateArgs,
+}
+
 /**
  * 引数を解析し、Config構造体を返す
  */
 pub fn get_args() -> MyResult<Config> {
-    let matches = App::new("catr")
-        .version("0.1.0")
-        .author("Hoge <hoge@example.com>")
-        .about("Rust cat")
-        .arg(
-            Arg::with_name("files")
-                .value_name("FILE")
-                .help("Input file(s)")
-                .multiple(true)
-                .default_value("-"),
-        )
-        .arg(
-            Arg::with_name("number")
-                .short("n")
-                .long("number")
-                .takes_value(false)
-                .help("Number all output lines"),
-        )
-        .get_matches();
-    
+    get_args_from(std::env::args_os())
+}
+
+/**
+ * 指定した引数列を解析し、Config構造体を返す
+ *
+ * 先頭の要素はプログラム名として扱われる
+ */
+pub fn get_args_from<I, T>(args: I) -> MyResult<Config>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let args = Args::parse_from(args);
+    args.generate.exit_if_requested::<Args>();
+
+    let show_all = args.show_all;
+    let e = args.e;
+    let t = args.t;
+
     Ok(Config {
-        files: matches.values_of_lossy("files").unwrap(), // 引数の値がUTF-8として解釈させるために values_of_lossy を使う
-        number_lines: matches.is_present("number"),
-        number_nonblank_lines: matches.is_present("number_nonblank"),
+        files: args.files,
+        number_lines: args.number,
+        number_nonblank_lines: args.number_nonblank,
+        show_ends: show_all || e || args.show_ends,
+        show_tabs: show_all || t || args.show_tabs,
+        show_nonprinting: show_all || e || t || args.show_nonprinting,
+        squeeze_blank: args.squeeze_blank,
+        decompress: args.decompress,
+        follow: args.follow,
+        retry: args.retry,
     })
 }
 
@@ -67,9 +490,125 @@ pub fn get_args() -> MyResult<Config> {
  * ファイル名が "-" の場合は標準入力から読み込む
  * それ以外の場合は引数に指定したファイルを開く
  */
-fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+fn open<'a>(
+    filename: &str,
+    stdin: &'a mut dyn BufRead,
+    decompress: bool,
+) -> MyResult<Input<'a>> {
+    let input = Input::open(filename, stdin)?;
+    if decompress {
+        Ok(input.decode_with(decode)?)
+    } else {
+        Ok(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cat_file, run_with, Compression, Config};
+    use std::io::Cursor;
+
+    fn config() -> Config {
+        Config::new(vec![])
+    }
+
+    fn cat(config: &Config, input: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        cat_file(config, Cursor::new(input), &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_plain_is_byte_exact() {
+        let input = b"a\r\nb\t\xff\x00\nno newline";
+        assert_eq!(cat(&config(), input), input);
+    }
+
+    #[test]
+    fn test_show_all() {
+        let config = Config {
+            show_ends: true,
+            show_tabs: true,
+            show_nonprinting: true,
+            ..config()
+        };
+        assert_eq!(
+            cat(&config, b"a\r\nb\tc\x01\x80\xff\x89\x7f\n"),
+            b"a^M$\nb^Ic^AM-^@M-^?M-^I^?$\n"
+        );
+    }
+
+    #[test]
+    fn test_show_ends_crlf() {
+        let config = Config {
+            show_ends: true,
+            ..config()
+        };
+        assert_eq!(cat(&config, b"a\r\nb\r"), b"a^M$\nb\r");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_squeeze_and_number_nonblank() {
+        let config = Config {
+            squeeze_blank: true,
+            number_nonblank_lines: true,
+            ..config()
+        };
+        assert_eq!(
+            cat(&config, b"a\n\n\n\nb\n"),
+            b"     1\ta\n\n     2\tb\n"
+        );
+    }
+
+    #[test]
+    fn test_detect_compression() {
+        assert_eq!(Compression::detect(b"\x1f\x8b\x08"), Some(Compression::Gzip));
+        assert_eq!(
+            Compression::detect(b"BZh91AY&SY"),
+            Some(Compression::Bzip2)
+        );
+        assert_eq!(
+            Compression::detect(b"BZh9\x17\x72\x45\x38\x50\x90"),
+            Some(Compression::Bzip2)
+        );
+        assert_eq!(Compression::detect(b"BZh91AY"), None);
+        assert_eq!(Compression::detect(b"BZhello, w"), None);
+        assert_eq!(
+            Compression::detect(b"\xfd7zXZ\x00"),
+            Some(Compression::Xz)
+        );
+        assert_eq!(
+            Compression::detect(b"\x28\xb5\x2f\xfd"),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(Compression::detect(b"hello"), None);
+        assert_eq!(Compression::detect(b"\x1f"), None);
+        assert_eq!(Compression::detect(b""), None);
+    }
+
+    #[test]
+    fn test_run_with() {
+        let config = Config::new(vec![
+            "-".to_string(),
+            "tests/inputs/fox.txt".to_string(),
+            "no-such-file".to_string(),
+        ])
+        .number_lines(true);
+        let mut stdin = Cursor::new("from stdin\n");
+        let mut out = vec![];
+        let mut err = vec![];
+
+        let res = run_with(config, &mut stdin, &mut out, &mut err);
+        assert!(res.is_err());
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                "     1\tfrom stdin\n",
+                "     1\tThe quick brown fox jumps over the lazy dog.\n",
+            )
+        );
+        assert!(String::from_utf8(err)
+            .unwrap()
+            .starts_with("catr: no-such-file: "));
+    }
+}
@@ -1,7 +1,5 @@
 fn main() {
     // catr::get_args()の戻り値がResult型なので、エラーがあればエラーメッセージを表示して終了する
-    if let Err(e) = catr::get_args().and_then(catr::run) {
-        eprintln!("{}", e);
-        std::process::exit(1);
-    } 
+    // ファイルごとのエラーは run の中で報告済みで、終了コードだけが非0になる
+    rcommon::exit_on_error("catr", catr::get_args().and_then(catr::run));
 }
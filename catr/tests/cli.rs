@@ -3,13 +3,28 @@ use assert_cmd::Command;
 use predicates::prelude::*;
 use pretty_assertions::assert_eq;
 use rand::{distributions::Alphanumeric, Rng};
+use rcommon::testing::{self, append, wait_for};
 use std::fs;
+use std::process::Child;
+use std::sync::mpsc::Receiver;
+use std::thread;
+use std::time::Duration;
+use tempfile::tempdir;
 
 const PRG: &str = "catr";
 const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const SPIDERS: &str = "tests/inputs/spiders.txt";
 const BUSTLE: &str = "tests/inputs/the-bustle.txt";
+const NONPRINT: &str = "tests/inputs/nonprint.txt";
+const BINARY: &str = "tests/inputs/binary.bin";
+const FOX_GZ: &str = "tests/inputs/fox.txt.gz";
+const FOX_BZ2: &str = "tests/inputs/fox.txt.bz2";
+const FOX_XZ: &str = "tests/inputs/fox.txt.xz";
+const FOX_ZST: &str = "tests/inputs/fox.txt.zst";
+const FOX_GZ_NOEXT: &str = "tests/inputs/fox-gzip-noext";
+const BZH_TEXT: &str = "tests/inputs/bzh.txt";
+const CORRUPT_GZ: &str = "tests/inputs/corrupt.gz";
 
 // --------------------------------------------------
 #[test]
@@ -23,6 +38,72 @@ fn usage() -> Result<()> {
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn completions() -> Result<()> {
+    let shells = [
+        ("bash", "_catr()"),
+        ("zsh", "#compdef catr"),
+        ("fish", "complete -c catr"),
+    ];
+    for (shell, expected) in shells {
+        Command::cargo_bin(PRG)?
+            .args(["--generate-completions", shell])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(expected))
+            .stdout(predicate::str::contains("show-nonprinting"));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_shell() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--generate-completions", "tcsh"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value 'tcsh'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn man_page() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .arg("--generate-man")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(".TH catr 1"))
+        .stdout(predicate::str::contains("Rust cat"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn file_named_man() -> Result<()> {
+    // "man" や "completions" はサブコマンドではなくファイル名として扱う
+    Command::cargo_bin(PRG)?
+        .current_dir("tests/inputs")
+        .arg("man")
+        .assert()
+        .success()
+        .stdout("This file is named man.\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_number_and_nonblank() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "-b", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
 // --------------------------------------------------
 fn gen_bad_file() -> String {
     loop {
@@ -42,11 +123,11 @@ fn gen_bad_file() -> String {
 #[test]
 fn skips_bad_file() -> Result<()> {
     let bad = gen_bad_file();
-    let expected = format!("{bad}: .* [(]os error 2[)]");
+    let expected = format!("^catr: {bad}: .* [(]os error 2[)]");
     Command::cargo_bin(PRG)?
         .arg(&bad)
         .assert()
-        .success()
+        .failure()
         .stderr(predicate::str::is_match(expected)?);
     Ok(())
 }
@@ -63,6 +144,17 @@ fn run(args: &[&str], expected_file: &str) -> Result<()> {
     Ok(())
 }
 
+// --------------------------------------------------
+fn run_bytes(args: &[&str], expected_file: &str) -> Result<()> {
+    // 入力が UTF-8 ではないので、バイト列のまま比較する
+    let expected = fs::read(expected_file)?;
+    let output = Command::cargo_bin(PRG)?.args(args).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, expected);
+
+    Ok(())
+}
+
 // --------------------------------------------------
 fn run_stdin(
     input_file: &str,
@@ -201,3 +293,339 @@ fn all_n() -> Result<()> {
 fn all_b() -> Result<()> {
     run(&[FOX, SPIDERS, BUSTLE, "-b"], "tests/expected/all.b.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn nonprint() -> Result<()> {
+    run_bytes(&[NONPRINT], NONPRINT)
+}
+
+// --------------------------------------------------
+#[test]
+fn nonprint_show_all() -> Result<()> {
+    run_bytes(&["-A", NONPRINT], "tests/expected/nonprint.txt.A.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn nonprint_show_all_long() -> Result<()> {
+    run_bytes(
+        &["--show-all", NONPRINT],
+        "tests/expected/nonprint.txt.A.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn nonprint_show_ends() -> Result<()> {
+    run_bytes(&["-E", NONPRINT], "tests/expected/nonprint.txt.E.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn nonprint_show_tabs() -> Result<()> {
+    run_bytes(&["-T", NONPRINT], "tests/expected/nonprint.txt.T.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn nonprint_show_nonprinting() -> Result<()> {
+    run_bytes(&["-v", NONPRINT], "tests/expected/nonprint.txt.v.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn nonprint_squeeze_blank() -> Result<()> {
+    run_bytes(&["-s", NONPRINT], "tests/expected/nonprint.txt.s.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn nonprint_squeeze_blank_n() -> Result<()> {
+    run_bytes(&["-s", "-n", NONPRINT], "tests/expected/nonprint.txt.sn.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn nonprint_squeeze_blank_b() -> Result<()> {
+    run_bytes(&["-sb", NONPRINT], "tests/expected/nonprint.txt.sb.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn binary() -> Result<()> {
+    run_bytes(&[BINARY], BINARY)
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_concat() -> Result<()> {
+    run_bytes(
+        &[BINARY, FOX, BINARY],
+        "tests/expected/binary.fox.binary.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_stdin() -> Result<()> {
+    let input = fs::read(BINARY)?;
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(input.clone())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, input);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn skips_directory() -> Result<()> {
+    let expected = fs::read_to_string("tests/expected/fox.txt.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", FOX])
+        .assert()
+        .failure()
+        .stdout(expected)
+        .stderr("catr: tests/inputs: Is a directory\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn gzip() -> Result<()> {
+    run(&["-z", FOX_GZ], "tests/expected/fox.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn bzip2() -> Result<()> {
+    run(&["-z", FOX_BZ2], "tests/expected/fox.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn xz() -> Result<()> {
+    run(&["--decompress", FOX_XZ], "tests/expected/fox.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn zstd() -> Result<()> {
+    run(&["-z", FOX_ZST], "tests/expected/fox.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn gzip_without_extension() -> Result<()> {
+    run(&["-z", FOX_GZ_NOEXT], "tests/expected/fox.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn plain_text_starting_with_bzh() -> Result<()> {
+    // "BZh" で始まるだけのテキストはそのまま出力する
+    run(&["-z", BZH_TEXT], "tests/expected/bzh.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn gzip_n() -> Result<()> {
+    run(&["-z", "-n", FOX_GZ], "tests/expected/fox.txt.n.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn gzip_without_flag() -> Result<()> {
+    // -z がなければ圧縮されたまま出力する
+    run_bytes(&[FOX_GZ], FOX_GZ)
+}
+
+// --------------------------------------------------
+#[test]
+fn decompress_plain() -> Result<()> {
+    run(&["-z", FOX, SPIDERS, BUSTLE], "tests/expected/all.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn decompress_stdin() -> Result<()> {
+    let input = fs::read(FOX_XZ)?;
+    let expected = fs::read_to_string("tests/expected/fox.txt.out")?;
+    Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .args(["-z", "-"])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn skips_corrupt_archive() -> Result<()> {
+    let expected = fs::read_to_string("tests/expected/fox.txt.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["-z", CORRUPT_GZ, FOX])
+        .assert()
+        .failure()
+        .stdout(expected)
+        .stderr(predicate::str::contains(format!("{CORRUPT_GZ}: gzip: ")));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_follow_and_number() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--follow", "-n", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_retry_without_follow() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--retry", FOX])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+// --------------------------------------------------
+// --follow のプロセスを起動し、標準出力をスレッドで読み続ける
+fn spawn_follow(args: &[&str]) -> Result<(Child, Receiver<Vec<u8>>)> {
+    let mut cmd = std::process::Command::new(assert_cmd::cargo::cargo_bin(PRG));
+    cmd.arg("--follow").args(args);
+    Ok(testing::spawn_follow(cmd)?)
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_appended() -> Result<()> {
+    let dir = tempdir()?;
+    let log = dir.path().join("build.log");
+    fs::write(&log, "one\n")?;
+
+    let (mut child, rx) = spawn_follow(&[log.to_str().unwrap()])?;
+    let mut output = vec![];
+    wait_for(&rx, &mut output, "one\n");
+
+    let writer = {
+        let log = log.clone();
+        thread::spawn(move || -> Result<()> {
+            append(&log, "two\n")?;
+            thread::sleep(Duration::from_millis(200));
+            append(&log, "three")?;
+            Ok(())
+        })
+    };
+    writer.join().unwrap()?;
+    wait_for(&rx, &mut output, "one\ntwo\nthree");
+
+    child.kill()?;
+    child.wait()?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_multiple_files() -> Result<()> {
+    let dir = tempdir()?;
+    let first = dir.path().join("first.log");
+    let second = dir.path().join("second.log");
+    fs::write(&first, "first\n")?;
+    fs::write(&second, "second\n")?;
+
+    let (mut child, rx) = spawn_follow(&[
+        first.to_str().unwrap(),
+        second.to_str().unwrap(),
+    ])?;
+    let mut output = vec![];
+    wait_for(&rx, &mut output, "first\nsecond\n");
+
+    append(&first, "more\n")?;
+    wait_for(&rx, &mut output, "first\nsecond\nmore\n");
+
+    child.kill()?;
+    child.wait()?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_truncated() -> Result<()> {
+    let dir = tempdir()?;
+    let log = dir.path().join("build.log");
+    fs::write(&log, "a long first line\n")?;
+
+    let (mut child, rx) = spawn_follow(&[log.to_str().unwrap()])?;
+    let mut output = vec![];
+    wait_for(&rx, &mut output, "a long first line\n");
+
+    fs::write(&log, "new\n")?;
+    wait_for(&rx, &mut output, "a long first line\nnew\n");
+
+    child.kill()?;
+    child.wait()?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_rotated() -> Result<()> {
+    let dir = tempdir()?;
+    let log = dir.path().join("app.log");
+    fs::write(&log, "old\n")?;
+
+    let (mut child, rx) = spawn_follow(&[log.to_str().unwrap()])?;
+    let mut output = vec![];
+    wait_for(&rx, &mut output, "old\n");
+
+    // ローテーション: 古いファイルを別名にして、同じ名前で新しいファイルを作る
+    fs::rename(&log, dir.path().join("app.log.1"))?;
+    fs::write(&log, "rotated\n")?;
+    wait_for(&rx, &mut output, "old\nrotated\n");
+
+    append(&log, "appended\n")?;
+    wait_for(&rx, &mut output, "old\nrotated\nappended\n");
+
+    child.kill()?;
+    child.wait()?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_retry() -> Result<()> {
+    let dir = tempdir()?;
+    let log = dir.path().join("late.log");
+
+    let (mut child, rx) = spawn_follow(&["--retry", log.to_str().unwrap()])?;
+    thread::sleep(Duration::from_millis(300));
+    append(&log, "finally\n")?;
+
+    let mut output = vec![];
+    wait_for(&rx, &mut output, "finally\n");
+
+    child.kill()?;
+    child.wait()?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_without_retry_exits() -> Result<()> {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["--follow", &bad])
+        .timeout(Duration::from_secs(10))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!("{bad}: ")));
+    Ok(())
+}
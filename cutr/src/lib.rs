@@ -1,11 +1,11 @@
 use crate::Extract::*;
 use anyhow::{anyhow, bail, Result};
-use clap::{App, Arg};
-use std::{error::Error, ops::Range, num::NonZeroUsize,
-    fs::File, io::{self, BufRead, BufReader}};
+use clap::{error::ErrorKind, ArgGroup, CommandFactory, Parser};
+use rcommon::cli::GenerateArgs;
+use rcommon::{open, MyResult, Reporter};
+use std::{ffi::OsString, io::{self, BufRead, Write}, ops::Range, num::NonZeroUsize};
 use regex::Regex;
 
-type MyResult<T> = Result<T, Box<dyn Error>>;
 type PositionList = Vec<Range<usize>>;
 
 #[derive(Debug)]
@@ -22,63 +22,89 @@ pub struct Config {
     extract: Extract,
 }
 
+impl Config {
+    /**
+     * 区切り文字をタブとして、extract で指定した部分を取り出す設定を作る
+     */
+    pub fn new(files: Vec<String>, extract: Extract) -> Config {
+        Config {
+            files,
+            delimiter: b'\t',
+            extract,
+        }
+    }
+
+    /**
+     * フィールドの区切り文字
+     */
+    pub fn delimiter(mut self, delimiter: u8) -> Config {
+        self.delimiter = delimiter;
+        self
+    }
+}
+
+// コマンドライン引数の定義 (ヘルプ・補完スクリプト・manページにも使われる)
+//
+// --fields, --bytes, --chars はどれか1つだけを指定できる (どれもない場合は get_args_from でエラーにする)
+#[derive(Debug, Parser)]
+#[command(
+    name = "cutr",
+    version = "0.1.0",
+    author = "hoge",
+    about = "Rust cut",
+    group(ArgGroup::new("extract").args(["fields", "bytes", "chars"]))
+)]
+struct Args {
+    /// Selected bytes
+    #[arg(short = 'b', long = "bytes", value_name = "BYTES")]
+    bytes: Option<String>,
+
+    /// Selected characters
+    #[arg(short = 'c', long = "chars", value_name = "CHARS")]
+    chars: Option<String>,
+
+    /// Field delimiter
+    #[arg(short = 'd', long = "delim", value_name = "DELIMITER", default_value = "\t")]
+    delimiter: String,
+
+    /// Selected fields
+    #[arg(short = 'f', long = "fields", value_name = "FIELDS")]
+    fields: Option<String>,
+
+    /// Input file(s)
+    #[arg(value_name = "FILE", default_value = "-")]
+    files: Vec<String>,
+
+    #[command(flatten)]
+    generate: GenerateArgs,
+}
+
 pub fn get_args() -> MyResult<Config> {
-    let matches = App::new("cutr")
-        .version("0.1.0")
-        .author("hoge")
-        .about("Rust cut")
-        .arg(
-            Arg::with_name("bytes")
-                .value_name("BYTES")
-                .help("Selected bytes")
-                .short("b")
-                .long("bytes")
-                .conflicts_with_all(&["bytes", "chars"])
-        )
-        .arg(
-            Arg::with_name("chars")
-                .value_name("CHARS")
-                .help("Selected characters")
-                .short("c")
-                .long("characters")
-                .conflicts_with_all(&["bytes", "chars"])
-                .takes_value(true)
-        )
-        .arg(
-            Arg::with_name("delimiter")
-                .value_name("DELIMITER")
-                .help("Field delimiter")
-                .long("delim")
-                .short("d")
-                .default_value("\t")
-        )
-        .arg(
-            Arg::with_name("fields")
-                .value_name("FIELDS")
-                .help("Selected fields")
-                .long("fields")
-                .short("f")
-
-                .conflicts_with_all(&["bytes", "chars"])
-        )
-        .arg(
-            Arg::with_name("files")
-                .value_name("FILE")
-                .help("Input file(s)")
-                .multiple(true)
-                .default_value("-")
-        )
-        .get_matches();
-
-    let delimiter = matches.value_of("delimiter").unwrap();
+    get_args_from(std::env::args_os())
+}
+
+/**
+ * 指定した引数列を解析し、Config構造体を返す
+ *
+ * 先頭の要素はプログラム名として扱われる
+ */
+pub fn get_args_from<I, T>(args: I) -> MyResult<Config>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let args = Args::parse_from(args);
+    args.generate.exit_if_requested::<Args>();
+
+    let delimiter = &args.delimiter;
     let delim_bytes = delimiter.as_bytes();
     if delim_bytes.len() != 1 {
         return Err(From::from(format!("--delim \"{}\" must be a single byte", delimiter)));
     }
 
-    let fields = matches.value_of("fields").map(parse_pos).transpose()?;
-    let bytes = matches.value_of("bytes").map(parse_pos).transpose()?;
-    let chars = matches.value_of("chars").map(parse_pos).transpose()?;
+    let fields = args.fields.as_deref().map(parse_pos).transpose()?;
+    let bytes = args.bytes.as_deref().map(parse_pos).transpose()?;
+    let chars = args.chars.as_deref().map(parse_pos).transpose()?;
 
     let extract = if let Some(field_pos) = fields {
         Fields(field_pos)
@@ -87,24 +113,48 @@ pub fn get_args() -> MyResult<Config> {
     } else if let Some(char_pos) = chars {
         Chars(char_pos)
     } else {
-        return Err(From::from("Must have -- fields, --bytes, or --chars"));
+        // --generate-* だけを指定できるように clap では必須にしていないので、同じエラーを返す
+        // (exit_on_error が clap と同じ形式で表示する)
+        return Err(Box::new(Args::command().error(
+            ErrorKind::MissingRequiredArgument,
+            "the following required arguments were not provided:\n  \
+            <--fields <FIELDS>|--bytes <BYTES>|--chars <CHARS>>",
+        )));
     };
 
     Ok(Config {
-        files: matches.values_of_lossy("files").unwrap(),
+        files: args.files,
         delimiter: *delim_bytes.first().unwrap(),
         extract,
     })
 }
 
 pub fn run(config: Config) -> MyResult<()> {
+    run_with(
+        config,
+        &mut io::stdin().lock(),
+        &mut io::stdout().lock(),
+        &mut io::stderr(),
+    )
+}
+
+/**
+ * 標準入力・標準出力・標準エラーの代わりに、指定したリーダーとライターを使って実行する
+ */
+pub fn run_with(
+    config: Config,
+    stdin: &mut dyn BufRead,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> MyResult<()> {
+    let mut reporter = Reporter::new("cutr", err);
     for filename in &config.files {
-        match open(filename) {
-            Err(err) => eprintln!("{}: {}", filename, err),
-            Ok(_) => println!("Opened {}", filename),
+        match open(filename, stdin) {
+            Err(err) => reporter.error(filename, err),
+            Ok(_) => writeln!(out, "Opened {}", filename)?,
         }
     }
-    Ok(())
+    reporter.finish()
 }
 
 /**
@@ -155,13 +205,6 @@ fn parse_pos(range: &str) -> Result<PositionList> {
 
 }
 
-fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
-    }
-}
-
 
 #[cfg(test)]
 mod unit_tests {
@@ -1,6 +1,3 @@
 fn main() {
-    if let Err(e) = cutr::get_args().and_then(cutr::run) {
-        eprintln!("{}", e);
-        std::process::exit(1);
-    }
+    rcommon::exit_on_error("cutr", cutr::get_args().and_then(cutr::run));
 }
@@ -37,7 +37,7 @@ fn skips_bad_file() -> Result<()> {
     Command::cargo_bin(PRG)?
         .args(["-f", "1", CSV, &bad, TSV])
         .assert()
-        .success()
+        .failure()
         .stderr(predicate::str::is_match(expected)?);
     Ok(())
 }
@@ -0,0 +1,100 @@
+use clap::Parser;
+use rcommon::cli::{GenerateArgs, GENERATE_IDS};
+use rcommon::MyResult;
+use std::ffi::OsString;
+use std::io::{self, BufRead, Write};
+
+#[derive(Debug)]
+pub struct Config {
+    text: Vec<String>,
+    omit_newline: bool, // 末尾の改行を省略するかどうか
+}
+
+impl Config {
+    pub fn new(text: Vec<String>) -> Config {
+        Config {
+            text,
+            omit_newline: false,
+        }
+    }
+
+    pub fn omit_newline(mut self, omit_newline: bool) -> Config {
+        self.omit_newline = omit_newline;
+        self
+    }
+}
+
+// コマンドライン引数の定義 (ヘルプ・補完スクリプト・manページにも使われる)
+// -h か --help でヘルプ、-V か --version でバージョンを表示する
+#[derive(Debug, Parser)]
+#[command(
+    name = "echor",
+    version = "0.1.0",
+    author = "Ken Youens-Clark <hoge@hoge.com>",
+    about = "Rust echo"
+)]
+struct Args {
+    /// Input text
+    #[arg(value_name = "TEXT", required_unless_present_any = GENERATE_IDS)] // 必須 (1つ以上)
+    text: Vec<String>,
+
+    /// Do not print newline
+    #[arg(short = 'n')] // -n で省略
+    omit_newline: bool,
+
+    #[command(flatten)]
+    generate: GenerateArgs,
+}
+
+pub fn get_args() -> MyResult<Config> {
+    get_args_from(std::env::args_os())
+}
+
+/**
+ * 指定した引数列を解析し、Config構造体を返す
+ *
+ * 先頭の要素はプログラム名として扱われる
+ */
+pub fn get_args_from<I, T>(args: I) -> MyResult<Config>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    // 引数を解析する
+    let args = Args::parse_from(args);
+    args.generate.exit_if_requested::<Args>();
+
+    Ok(Config {
+        text: args.text,
+        omit_newline: args.omit_newline,
+    })
+}
+
+pub fn run(config: Config) -> MyResult<()> {
+    run_with(
+        config,
+        &mut io::stdin().lock(),
+        &mut io::stdout().lock(),
+        &mut io::stderr(),
+    )
+}
+
+/**
+ * 標準出力の代わりに、指定したライターへ出力する
+ *
+ * 他のコマンドと呼び出し方を揃えるために入力と標準エラーも受け取るが、使わない
+ */
+pub fn run_with(
+    config: Config,
+    _stdin: &mut dyn BufRead,
+    out: &mut dyn Write,
+    _err: &mut dyn Write,
+) -> MyResult<()> {
+    write!(
+        out,
+        "{}{}",
+        config.text.join(" "),
+        if config.omit_newline { "" } else { "\n" }
+    )?;
+    Ok(())
+}
@@ -20,7 +20,7 @@ fn dies_no_args() -> TestResult {
     Command::cargo_bin("echor")?
         .assert()
         .failure()
-        .stderr(predicate::str::contains("USAGE"));
+        .stderr(predicate::str::contains("Usage"));
 
     Ok(())
 }
@@ -48,6 +48,22 @@ fn hello2() -> TestResult {
     run(&["Hello", "there"], "tests/expected/hello2.txt")
 }
 
+#[test]
+fn word_man() -> TestResult {
+    // "man" や "completions" はサブコマンドではなく文字列として扱う
+    Command::cargo_bin("echor")?
+        .arg("man")
+        .assert()
+        .success()
+        .stdout("man\n");
+    Command::cargo_bin("echor")?
+        .args(["completions", "bash"])
+        .assert()
+        .success()
+        .stdout("completions bash\n");
+    Ok(())
+}
+
 #[test]
 fn hello1_no_newline() -> TestResult {
     run(&["Hello", "there", "-n"], "tests/expected/hello1.n.txt")
@@ -1,15 +1,19 @@
 use crate::EntryType::*;
-use clap::{App, Arg};
+use clap::{Parser, ValueEnum};
+use rcommon::cli::GenerateArgs;
+use rcommon::MyResult;
 use regex::Regex;
-use std::error::Error;
+use std::ffi::OsString;
+use std::io::{self, BufRead, Write};
 use walkdir::WalkDir;
 
-type MyResult<T> = Result<T, Box<dyn Error>>;
-
-#[derive(Debug, Eq, PartialEq)]
-enum EntryType {
+#[derive(Debug, Eq, PartialEq, Clone, Copy, ValueEnum)]
+pub enum EntryType {
+    #[value(name = "d")]
     Dir,
+    #[value(name = "f")]
     File,
+    #[value(name = "l")]
     Link,
 }
 
@@ -20,76 +24,112 @@ pub struct Config {
     entry_types: Vec<EntryType>,
 }
 
+impl Config {
+    /**
+     * paths 以下の全てのエントリを出力する設定を作る
+     */
+    pub fn new(paths: Vec<String>) -> Config {
+        Config {
+            paths,
+            names: vec![],
+            entry_types: vec![],
+        }
+    }
+
+    /**
+     * いずれかにマッチする名前のエントリだけを出力する
+     */
+    pub fn names(mut self, names: Vec<Regex>) -> Config {
+        self.names = names;
+        self
+    }
+
+    /**
+     * いずれかの種類のエントリだけを出力する
+     */
+    pub fn entry_types(mut self, entry_types: Vec<EntryType>) -> Config {
+        self.entry_types = entry_types;
+        self
+    }
+}
+
+// コマンドライン引数の定義 (ヘルプ・補完スクリプト・manページにも使われる)
+#[derive(Debug, Parser)]
+#[command(
+    name = "findr",
+    version = "0.1.0",
+    author = "hoge",
+    about = "Rust find"
+)]
+struct Args {
+    /// Path to start searching
+    #[arg(value_name = "PATH", default_value = ".")]
+    paths: Vec<String>,
+
+    /// Name
+    #[arg(short = 'n', long = "name", value_name = "NAME", num_args = 1..)]
+    names: Vec<String>,
+
+    /// Entry type
+    #[arg(short = 't', long = "type", value_name = "TYPE", num_args = 1..)]
+    entry_types: Vec<EntryType>,
+
+    #[command(flatten)]
+    generate: GenerateArgs,
+}
+
 pub fn get_args() -> MyResult<Config> {
-    let matches = App::new("findr")
-        .version("0.1.0")
-        .author("hoge")
-        .about("Rust find")
-        .arg(
-            Arg::with_name("paths")
-                .value_name("PATH")
-                .help("Path to start searching")
-                .default_value(".")
-                .multiple(true)
-        )
-        .arg(
-            Arg::with_name("names")
-                .value_name("NAME")
-                .help("Name")
-                .long("name")
-                .short("n")
-                .multiple(true)
-                .takes_value(true)
-        )
-        .arg(
-            Arg::with_name("types")
-                .value_name("TYPE")
-                .short("t")
-                .long("type")
-                .help("Entry type")
-                .possible_values(&["d", "f", "l"])
-                .multiple(true)
-                .takes_value(true)
-        )
-        .get_matches();
-
-    let names = matches
-        .values_of_lossy("names")
-        .map(|values| {
-            values.into_iter()
-                .map(|v| {
-                    Regex::new(&v)
-                        .map_err(|_| format!("Invalid --name \"{}\"", v))
-                })
-                .collect::<Result<Vec<_>, _>>()
-        })
-        .transpose()?
-        .unwrap_or_default();
-    let entry_types = matches
-        .values_of_lossy("types")
-        .map(|values| {
-            values.iter()
-                .map(|v| {
-                    match v.as_str() {
-                        "d" => Dir,
-                        "f" => File,
-                        "l" => Link,
-                        _ => unreachable!("Invalid type"),
-                    }
-                })
-                .collect()
+    get_args_from(std::env::args_os())
+}
+
+/**
+ * 指定した引数列を解析し、Config構造体を返す
+ *
+ * 先頭の要素はプログラム名として扱われる
+ */
+pub fn get_args_from<I, T>(args: I) -> MyResult<Config>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let args = Args::parse_from(args);
+    args.generate.exit_if_requested::<Args>();
+
+    let names = args
+        .names
+        .into_iter()
+        .map(|v| {
+            Regex::new(&v)
+                .map_err(|_| format!("Invalid --name \"{}\"", v))
         })
-        .unwrap_or_default();
+        .collect::<Result<Vec<_>, _>>()?;
     Ok(Config {
-        paths: matches
-            .values_of_lossy("paths")
-            .unwrap_or_default(),
+        paths: args.paths,
         names,
-        entry_types
+        entry_types: args.entry_types,
     })
 }
 
 pub fn run(config: Config) -> MyResult<()> {
+    run_with(
+        config,
+        &mut io::stdin().lock(),
+        &mut io::stdout().lock(),
+        &mut io::stderr(),
+    )
+}
+
+/**
+ * 標準出力・標準エラーの代わりに、指定したライターを使って実行する
+ *
+ * 他のコマンドと呼び出し方を揃えるために入力も受け取るが、使わない
+ */
+pub fn run_with(
+    config: Config,
+    _stdin: &mut dyn BufRead,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> MyResult<()> {
 
     let type_filter = |entry: &walkdir::DirEntry| {
         config.entry_types.is_empty()
@@ -113,7 +153,8 @@ pub fn run(config: Config) -> MyResult<()> {
             .into_iter()
             .filter_map(|e| match e {
                 Err(e) => {
-                    eprintln!("{}", e);
+                    // 標準エラーに書けない場合は報告のしようがないので無視する
+                    let _ = writeln!(err, "{}", e);
                     None
                 }
                 Ok(e) => Some(e),
@@ -122,7 +163,7 @@ pub fn run(config: Config) -> MyResult<()> {
             .filter(name_filter)
             .map(|e| e.path().display().to_string())
             .collect::<Vec<_>>();
-        println!("{}", entries.join("\n"));
+        writeln!(out, "{}", entries.join("\n"))?;
     }
     Ok(())
 }
\ No newline at end of file
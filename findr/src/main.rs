@@ -1,6 +1,3 @@
 fn main() {
-    if let Err(e) = findr::get_args().and_then(findr::run) {
-        eprintln!("{}", e);
-        std::process::exit(1);
-    }
+    rcommon::exit_on_error("findr", findr::get_args().and_then(findr::run));
 }
@@ -1,129 +1,447 @@
-use clap::{App, Arg};
-use std::error::Error;
+use clap::Parser;
+use rcommon::cli::GenerateArgs;
+use rcommon::size::parse_size;
+use rcommon::{Input, MyResult, Reporter};
+use std::collections::VecDeque;
+use std::ffi::OsString;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, Read};
+use std::io::{self, BufRead, Read, Write};
 
-type MyResult<T> = Result<T, Box<dyn Error>>;
+/**
+ * 出力する行数・バイト数
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Count {
+    First(usize),      // 先頭から K 個 (-n K)
+    AllButLast(usize), // 末尾の K 個を除いた全て (-n -K)
+}
+
+impl From<usize> for Count {
+    fn from(n: usize) -> Count {
+        Count::First(n)
+    }
+}
 
 // usize: 符号なし整数型
 #[derive(Debug)]
 pub struct Config {
     files: Vec<String>,
-    lines: usize,
-    bytes: Option<usize>
+    lines: Count,
+    bytes: Option<Count>,
+    chars: Option<usize>,  // 行数の代わりに、先頭から出力する文字数
+    headers: Option<bool>, // ファイル名を表示するかどうか (None の場合は複数のファイルのときだけ)
+    delimiter: u8,         // 行の区切り (-z の場合は NUL)
+}
+
+impl Config {
+    /**
+     * 各ファイルの先頭10行を出力する設定を作る
+     */
+    pub fn new(files: Vec<String>) -> Config {
+        Config {
+            files,
+            lines: Count::First(10),
+            bytes: None,
+            chars: None,
+            headers: None,
+            delimiter: b'\n',
+        }
+    }
+
+    /**
+     * 出力する行数 (Count::AllButLast の場合は末尾の行を除いて出力する)
+     */
+    pub fn lines(mut self, lines: impl Into<Count>) -> Config {
+        self.lines = lines.into();
+        self
+    }
+
+    /**
+     * 行数の代わりに、出力するバイト数を指定する
+     */
+    pub fn bytes(mut self, bytes: impl Into<Count>) -> Config {
+        self.bytes = Some(bytes.into());
+        self
+    }
+
+    /**
+     * 行数の代わりに、出力する文字 (Unicode のスカラー値) の数を指定する
+     *
+     * 文字の途中で切れることはない
+     */
+    pub fn chars(mut self, chars: usize) -> Config {
+        self.chars = Some(chars);
+        self
+    }
+
+    /**
+     * ファイルの数にかかわらず、"==> file <==" を表示する (true) か表示しない (false)
+     */
+    pub fn headers(mut self, headers: bool) -> Config {
+        self.headers = Some(headers);
+        self
+    }
+
+    /**
+     * 行の区切りにするバイト (-z の場合は b'\0')
+     */
+    pub fn delimiter(mut self, delimiter: u8) -> Config {
+        self.delimiter = delimiter;
+        self
+    }
+}
+
+// コマンドライン引数の定義 (ヘルプ・補完スクリプト・manページにも使われる)
+#[derive(Debug, Parser)]
+#[command(
+    name = "headr",
+    version = "0.1.0",
+    author = "hoge",
+    about = "rust head command"
+)]
+struct Args {
+    /// Number of lines (with a leading '-', print all but the last LINES lines;
+    /// suffixes such as K, MB or GiB are allowed)
+    #[arg(
+        short = 'n',
+        long = "lines",
+        value_name = "LINES",
+        default_value = "10",
+        allow_hyphen_values = true
+    )]
+    lines: String,
+
+    /// Number of bytes (with a leading '-', print all but the last BYTES bytes;
+    /// b = 512, K = KiB = 1024, KB = 1000, and so on for M, G, T, P, E)
+    #[arg(
+        short = 'c',
+        long = "bytes",
+        value_name = "BYTES",
+        conflicts_with = "lines",
+        allow_hyphen_values = true
+    )]
+    bytes: Option<String>,
+
+    /// Number of characters (never splits a multibyte UTF-8 character)
+    #[arg(
+        long = "chars",
+        value_name = "CHARS",
+        conflicts_with_all = ["lines", "bytes"]
+    )]
+    chars: Option<String>,
+
+    /// Never print headers giving file names
+    #[arg(
+        short = 'q',
+        long = "quiet",
+        visible_alias = "silent",
+        overrides_with = "verbose"
+    )]
+    quiet: bool,
+
+    /// Always print headers giving file names
+    #[arg(short = 'v', long = "verbose", overrides_with = "quiet")]
+    verbose: bool,
+
+    /// Line delimiter is NUL, not newline
+    #[arg(short = 'z', long = "zero-terminated")]
+    zero_terminated: bool,
+
+    /// Input file(s)
+    #[arg(value_name = "FILE", default_value = "-")]
+    files: Vec<String>,
+
+    #[command(flatten)]
+    generate: GenerateArgs,
 }
 
 pub fn get_args() -> MyResult<Config> {
-    let matches = App::new("headr")
-        .version("0.1.0")
-        .author("hoge")
-        .about("rust head command")
-        .arg(
-            Arg::with_name("lines")
-                .value_name("LINES")
-                .short("n")
-                .long("lines")
-                .help("Number of lines")
-                .default_value("10")
-        )
-        .arg(
-            Arg::with_name("bytes")
-                .value_name("BYTES")
-                .short("c")
-                .long("bytes")
-                .takes_value(true)
-                .help("Number of files")
-                .conflicts_with("lines")
-        )
-        .arg(
-            Arg::with_name("files")
-                .value_name("FILE")
-                .help("Input file(s)")
-                .multiple(true)
-                .default_value("-"),
-        )
-
-        .get_matches();
+    get_args_from(std::env::args_os())
+}
+
+/**
+ * 指定した引数列を解析し、Config構造体を返す
+ *
+ * 先頭の要素はプログラム名として扱われる
+ */
+pub fn get_args_from<I, T>(args: I) -> MyResult<Config>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let args = Args::parse_from(args);
+    args.generate.exit_if_requested::<Args>();
 
     // 引数のパース
-    let lines = matches
-        .value_of("lines")
-        .map(parse_positive_int)
+    let lines = parse_count(&args.lines).map_err(|e| format!("illegal line count -- {}", e))?;
+
+    let bytes = args
+        .bytes
+        .as_deref()
+        .map(parse_count)
         .transpose() // Option<Result<T, E>> -> Result<Option<T>, E>
-        .map_err(|e| format!("illegal line count -- {}", e))?;
+        .map_err(|e| format!("illegal byte count -- {}", e))?;
 
-    let bytes = matches
-        .value_of("bytes")
+    let chars = args
+        .chars
+        .as_deref()
         .map(parse_positive_int)
         .transpose()
-        .map_err(|e| format!("illegal byte count -- {}", e))?;
+        .map_err(|e| format!("illegal character count -- {}", e))?;
 
+    let headers = match (args.quiet, args.verbose) {
+        (true, _) => Some(false),
+        (_, true) => Some(true),
+        _ => None,
+    };
 
     Ok(Config {
-        files: matches.values_of_lossy("files").unwrap(),
-        lines: lines.unwrap(),
+        files: args.files,
+        lines,
         bytes,
+        chars,
+        headers,
+        delimiter: if args.zero_terminated { b'\0' } else { b'\n' },
     })
 }
 
 pub fn run(config: Config) -> MyResult<()> {
+    run_with(
+        config,
+        &mut io::stdin().lock(),
+        &mut io::stdout().lock(),
+        &mut io::stderr(),
+    )
+}
 
-    let num_files = config.files.len();
+/**
+ * 標準入力・標準出力・標準エラーの代わりに、指定したリーダーとライターを使って実行する
+ */
+pub fn run_with(
+    config: Config,
+    stdin: &mut dyn BufRead,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> MyResult<()> {
+    let mut reporter = Reporter::new("headr", err);
+    let headers = config.headers.unwrap_or(config.files.len() > 1);
 
     for (file_num, filename) in config.files.iter().enumerate() {
-        match open(&filename) {
-            Err(err) => eprintln!("{}: {}", filename, err),
-            Ok(mut file) => {
-
-                // 複数のファイルが指定されている場合は、ファイル名を表示
-                if num_files > 1 {
-                    println!(
+        match Input::open(filename, stdin) {
+            Err(err) => reporter.error(filename, err),
+            Ok(file) => {
+                // 複数のファイルが指定されている場合 (または -v の場合) は、ファイル名を表示
+                if headers {
+                    writeln!(
+                        out,
                         "{}==> {} <==",
                         if file_num > 0 { "\n" } else { "" },
                         filename
-                    );
+                    )?;
                 }
 
+                if let Err(err) = head(&config, file, out) {
+                    reporter.error(filename, err);
+                }
+            }
+        }
+    }
+
+    reporter.finish()
+}
+
+/**
+ * 1つの入力の先頭部分を出力する
+ */
+fn head(config: &Config, input: Input, out: &mut dyn Write) -> MyResult<()> {
+    if let Some(num_chars) = config.chars {
+        return Ok(first_chars(input.into_reader(), num_chars, out)?);
+    }
+    let mut file = match (config.bytes, input) {
+        (Some(count), Input::File(file)) if known_size(&file, count)? => {
+            return Ok(file_bytes(file, count, out)?);
+        }
+        (_, input) => input.into_reader(),
+    };
+    match (config.bytes, config.lines) {
+        (Some(Count::First(num_bytes)), _) => {
+            // 指定したバイト数に達するか EOF になるまで、固定長のバッファで読んでは書く
+            // (パイプなどで1回の read が短く返っても、続きを読み続ける)
+            io::copy(&mut file.take(num_bytes as u64), out)?;
+        }
+        (Some(Count::AllButLast(num_bytes)), _) => all_but_last_bytes(file, num_bytes, out)?,
+        (None, Count::First(num_lines)) => {
+            let mut line = vec![];
+            for _ in 0..num_lines {
+                let bytes = file.read_until(config.delimiter, &mut line)?;
 
-                if let Some(num_bytes) = config.bytes {
-                    // 指定したバイト数だけ読み込む
-                    let mut handle = file.take(num_bytes as u64);
-                    // ファイルから読み込んだバイトを保持するために、0で初期化したnum_bytes長の可変バッファの作成
-                    let mut buffer = vec![0; num_bytes];
-                    let bytes_read = handle.read(&mut buffer)?;
-
-                    // 実際に読み込まれたバイト数を文字列に変換して出力
-                    print!("{}", String::from_utf8_lossy(&buffer[..bytes_read]));
-                } else {
-                    let mut line = String::new();
-                    for _ in 0..config.lines {
-                        let bytes = file.read_line(&mut line)?;
-
-                        // EOFに達すると0byteが返される
-                        if bytes == 0 {
-                            break;
-                        }
-                        print!("{}", line);
-                        // バッファをクリア
-                        line.clear();
-                    }
+                // EOFに達すると0byteが返される
+                if bytes == 0 {
+                    break;
                 }
-            } 
+                out.write_all(&line)?;
+                // バッファをクリア
+                line.clear();
+            }
+        }
+        (None, Count::AllButLast(num_lines)) => {
+            all_but_last_lines(file, num_lines, config.delimiter, out)?
+        }
+    }
+    Ok(())
+}
+
+/**
+ * file_bytes で扱える、大きさの分かるファイルかどうか
+ *
+ * /proc のファイルのように大きさが 0 と報告されるものは、末尾を除く長さを先に決められない
+ */
+fn known_size(file: &File, count: Count) -> io::Result<bool> {
+    let metadata = file.metadata()?;
+    Ok(metadata.is_file() && (metadata.len() > 0 || matches!(count, Count::First(_))))
+}
+
+/**
+ * 通常のファイルの先頭部分をバイト数で出力する
+ *
+ * ファイルの大きさが分かるので、末尾を除く場合もリングバッファを使わずに、
+ * 出力する長さを先に決めてから固定長のバッファでコピーする
+ */
+fn file_bytes(file: File, count: Count, out: &mut dyn Write) -> io::Result<()> {
+    let len = match count {
+        Count::First(num_bytes) => num_bytes as u64,
+        Count::AllButLast(num_bytes) => file.metadata()?.len().saturating_sub(num_bytes as u64),
+    };
+    io::copy(&mut file.take(len), out)?;
+    Ok(())
+}
+
+/**
+ * 先頭の num_chars 文字を出力する
+ *
+ * UTF-8の継続バイト以外のバイトを文字の先頭として数え、num_chars + 1 文字目の先頭の手前まで出力する。
+ * 入力はそのまま出力するので、不正なUTF-8の継続バイトは直前の文字の一部として扱われる
+ */
+fn first_chars(mut file: impl BufRead, num_chars: usize, out: &mut dyn Write) -> io::Result<()> {
+    let mut remaining = num_chars;
+    loop {
+        let buf = match file.fill_buf() {
+            Ok(buf) => buf,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if buf.is_empty() {
+            return Ok(());
+        }
+
+        let starts = buf.iter().enumerate().filter(|(_, &b)| b & 0xc0 != 0x80);
+        for (pos, _) in starts {
+            if remaining == 0 {
+                return out.write_all(&buf[..pos]);
+            }
+            remaining -= 1;
+        }
+        out.write_all(buf)?;
+        let len = buf.len();
+        file.consume(len);
+    }
+}
+
+/**
+ * 末尾の num_bytes バイトを除いて出力する
+ *
+ * 最後の num_bytes バイトだけをリングバッファに残し、押し出されたバイトから順に出力するので、
+ * 入力の大きさにかかわらず使うメモリは num_bytes と読み込みバッファの分だけで済む
+ */
+fn all_but_last_bytes(
+    mut file: impl BufRead,
+    num_bytes: usize,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let mut ring: VecDeque<u8> = VecDeque::new();
+    loop {
+        let buf = match file.fill_buf() {
+            Ok(buf) => buf,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if buf.is_empty() {
+            break;
         }
+        let len = buf.len();
+
+        // 末尾の num_bytes バイトに入らないことが確定した分は、古いものから出力する
+        let excess = (ring.len() + len).saturating_sub(num_bytes);
+        let from_ring = excess.min(ring.len());
+        let (front, back) = ring.as_slices();
+        let split = from_ring.min(front.len());
+        out.write_all(&front[..split])?;
+        out.write_all(&back[..from_ring - split])?;
+        ring.drain(..from_ring);
+
+        let from_buf = excess - from_ring;
+        out.write_all(&buf[..from_buf])?;
+        ring.extend(&buf[from_buf..]);
+        file.consume(len);
     }
+    Ok(())
+}
 
-    Ok(()) 
+/**
+ * 末尾の num_lines 行 (delimiter で区切った行) を除いて出力する
+ *
+ * 最後の num_lines 行だけをリングバッファに残す。押し出された行のバッファは次の行に使い回す
+ */
+fn all_but_last_lines(
+    mut file: impl BufRead,
+    num_lines: usize,
+    delimiter: u8,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let mut ring: VecDeque<Vec<u8>> = VecDeque::new();
+    let mut line = vec![];
+    loop {
+        line.clear();
+        if file.read_until(delimiter, &mut line)? == 0 {
+            break;
+        }
+        ring.push_back(line);
+        line = if ring.len() > num_lines {
+            let oldest = ring.pop_front().unwrap_or_default();
+            out.write_all(&oldest)?;
+            oldest
+        } else {
+            vec![]
+        };
+    }
+    Ok(())
+}
+
+/**
+ * 行数・バイト数の引数を変換する
+ *
+ * 先頭に '-' がある場合は末尾を除く数として扱う (-0 は全て)
+ */
+fn parse_count(val: &str) -> MyResult<Count> {
+    match val.strip_prefix('-') {
+        Some(num) => parse_size(num)
+            .map(Count::AllButLast)
+            .map_err(|e| e.describe(val)),
+        None => parse_positive_int(val).map(Count::First),
+    }
 }
 
 /**
  * コマンドライン引数の文字列を正の整数に変換する
+ *
+ * 10K や 4MiB のような単位も使える (parse_size)
  */
 fn parse_positive_int(val: &str) -> MyResult<usize> {
-
     // 0より大きい整数に変換できる場合はOk、それ以外はErr
-    match val.parse() {
+    match parse_size(val) {
         Ok(n) if n > 0 => Ok(n),
-        _ => Err(From::from(val)) // 数値に変換できない場合はエラー
+        Ok(_) => Err(From::from(val)),
+        Err(e) => Err(e.describe(val)), // 数値に変換できない場合はエラー
     }
 }
 
@@ -146,12 +464,135 @@ fn test_parse_positive_int() {
     assert_eq!(res.unwrap_err().to_string(), "0".to_string());
 }
 
-/**
- * ファイルopen
- */
-fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(std::io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+#[test]
+fn test_parse_count() {
+    assert_eq!(parse_count("3").unwrap(), Count::First(3));
+    assert_eq!(parse_count("-3").unwrap(), Count::AllButLast(3));
+    assert_eq!(parse_count("-0").unwrap(), Count::AllButLast(0));
+
+    // 0 や数字でない文字列はエラー
+    assert_eq!(parse_count("0").unwrap_err().to_string(), "0");
+    assert_eq!(parse_count("-foo").unwrap_err().to_string(), "-foo");
+    assert_eq!(parse_count("--3").unwrap_err().to_string(), "--3");
+    assert_eq!(parse_count("-2K").unwrap(), Count::AllButLast(2048));
+    assert_eq!(
+        parse_count("-16E").unwrap_err().to_string(),
+        "-16E: value too large"
+    );
+}
+
+#[test]
+fn test_all_but_last() {
+    let text = "one\ntwo\nthree\nfour";
+    let lines = |n| {
+        let mut out = vec![];
+        all_but_last_lines(text.as_bytes(), n, b'\n', &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!(lines(0), text);
+    assert_eq!(lines(1), "one\ntwo\nthree\n");
+    assert_eq!(lines(3), "one\n");
+    assert_eq!(lines(10), "");
+
+    let mut out = vec![];
+    all_but_last_lines(&b"a\0b\nc\0d"[..], 1, b'\0', &mut out).unwrap();
+    assert_eq!(out, b"a\0b\nc\0");
+
+    // 読み込みバッファより短い単位で読んでも同じ結果になる
+    for capacity in 1..5 {
+        for n in 0..=text.len() + 1 {
+            let reader = io::BufReader::with_capacity(capacity, text.as_bytes());
+            let mut out = vec![];
+            all_but_last_bytes(reader, n, &mut out).unwrap();
+            assert_eq!(out, &text.as_bytes()[..text.len().saturating_sub(n)]);
+        }
+    }
+}
+
+#[test]
+fn test_first_chars() {
+    let text = "aé日本\u{1f980}z";
+    let chars = |n, capacity| {
+        let reader = io::BufReader::with_capacity(capacity, text.as_bytes());
+        let mut out = vec![];
+        first_chars(reader, n, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    // 読み込みバッファが文字の途中で切れても、文字の途中では切らない
+    for capacity in 1..8 {
+        assert_eq!(chars(0, capacity), "");
+        assert_eq!(chars(2, capacity), "aé");
+        assert_eq!(chars(4, capacity), "aé日本");
+        assert_eq!(chars(5, capacity), "aé日本\u{1f980}");
+        assert_eq!(chars(10, capacity), text);
+    }
+
+    // 不正なバイト列はそのまま出力する
+    let mut out = vec![];
+    first_chars(&b"\xffa\x80b"[..], 2, &mut out).unwrap();
+    assert_eq!(out, b"\xffa\x80");
+}
+
+#[test]
+fn test_run_with() {
+    let config = Config::new(vec!["-".to_string(), "tests/inputs/two.txt".to_string()]).lines(1);
+    let mut stdin = io::Cursor::new("first\nsecond\n");
+    let mut out = vec![];
+    let mut err = vec![];
+
+    assert!(run_with(config, &mut stdin, &mut out, &mut err).is_ok());
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "==> - <==\nfirst\n\n==> tests/inputs/two.txt <==\nTwo lines.\n"
+    );
+    assert!(err.is_empty());
+
+    let config = Config::new(vec!["tests/inputs/two.txt".to_string()])
+        .lines(1)
+        .headers(true);
+    let mut out = vec![];
+    assert!(run_with(config, &mut io::empty(), &mut out, &mut io::sink()).is_ok());
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "==> tests/inputs/two.txt <==\nTwo lines.\n"
+    );
+
+    let config = Config::new(vec!["-".to_string()]).lines(2).delimiter(b'\0');
+    let mut stdin = io::Cursor::new("a\0b\nc\0d\0");
+    let mut out = vec![];
+    assert!(run_with(config, &mut stdin, &mut out, &mut io::sink()).is_ok());
+    assert_eq!(out, b"a\0b\nc\0");
+
+    let config = Config::new(vec!["no-such-file".to_string()]).bytes(2);
+    let mut out = vec![];
+    let mut err = vec![];
+    assert!(run_with(config, &mut io::empty(), &mut out, &mut err).is_err());
+    assert!(out.is_empty());
+    assert!(String::from_utf8(err)
+        .unwrap()
+        .starts_with("headr: no-such-file: "));
+}
+
+#[test]
+fn test_bytes_short_reads() {
+    // 1回の read で最大3バイトしか返さないパイプのような入力
+    struct ShortReads<'a>(&'a [u8]);
+
+    impl Read for ShortReads<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(3);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
     }
-}
\ No newline at end of file
+
+    let text = b"one\ntwo\nthree\n";
+    for num_bytes in [1, 5, 10, text.len(), 100] {
+        let config = Config::new(vec!["-".to_string()]).bytes(num_bytes);
+        let mut stdin = io::BufReader::with_capacity(2, ShortReads(text));
+        let mut out = vec![];
+        assert!(run_with(config, &mut stdin, &mut out, &mut io::sink()).is_ok());
+        assert_eq!(out, &text[..num_bytes.min(text.len())]);
+    }
+}
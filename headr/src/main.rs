@@ -1,9 +1,3 @@
 fn main() {
-
-    if let Err(e) = headr::get_args().and_then(headr::run) {
-        eprintln!("{}", e);
-        std::process::exit(1);
-    }
+    rcommon::exit_on_error("headr", headr::get_args().and_then(headr::run));
 }
-
-
@@ -70,8 +70,7 @@ fn dies_bytes_and_lines() -> Result<()> {
         .args(["-n", "1", "-c", "2"])
         .assert()
         .failure()
-       // 変更前: .stderr(predicate::str::contains("the argument '--lines <LINES>' cannot be used with '--bytes <BYTES>'"))
-       .stderr(predicate::str::contains("error: The argument '--lines <LINES>' cannot be used with '--bytes <BYTES>'"));
+        .stderr(predicate::str::contains(msg));
 
     Ok(())
 }
@@ -128,6 +127,19 @@ fn run_stdin(
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn file_named_man() -> Result<()> {
+    // "man" はサブコマンドではなくファイル名として扱う
+    Command::cargo_bin(PRG)?
+        .current_dir("tests/inputs")
+        .arg("man")
+        .assert()
+        .success()
+        .stdout("This file is named man.\n");
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn empty() -> Result<()> {
@@ -417,3 +429,289 @@ fn multiple_files_c4() -> Result<()> {
         "tests/expected/all.c4.out",
     )
 }
+
+// --------------------------------------------------
+#[test]
+fn twelve_n_minus3() -> Result<()> {
+    let expected = "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\n";
+    Command::cargo_bin(PRG)?
+        .args(["-n", "-3", TWELVE])
+        .assert()
+        .success()
+        .stdout(expected);
+    Command::cargo_bin(PRG)?
+        .args(["-n-3", TWELVE])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+#[test]
+fn twelve_n_minus0_stdin() -> Result<()> {
+    run_stdin(&["-n", "-0"], TWELVE, TWELVE)
+}
+
+#[test]
+fn three_n_minus1_stdin() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--lines=-1"])
+        .write_stdin(fs::read(THREE)?)
+        .assert()
+        .success()
+        .stdout("Three\r\nlines,\r\n");
+    Ok(())
+}
+
+#[test]
+fn two_n_minus4() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "-4", TWO])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+#[test]
+fn twelve_c_minus10_stdin() -> Result<()> {
+    let input = fs::read(TWELVE)?;
+    Command::cargo_bin(PRG)?
+        .args(["-c", "-10"])
+        .write_stdin(input.clone())
+        .assert()
+        .success()
+        .stdout(predicate::eq(&input[..input.len() - 10]));
+    Ok(())
+}
+
+#[test]
+fn all_c_minus2() -> Result<()> {
+    let mut expected = vec![];
+    for (num, file) in [EMPTY, ONE, TWO].iter().enumerate() {
+        let contents = fs::read(file)?;
+        let sep = if num > 0 { "\n" } else { "" };
+        expected.extend(format!("{sep}==> {file} <==\n").bytes());
+        expected.extend(&contents[..contents.len().saturating_sub(2)]);
+    }
+    Command::cargo_bin(PRG)?
+        .args(["-c", "-2", EMPTY, ONE, TWO])
+        .assert()
+        .success()
+        .stdout(predicate::eq(expected));
+    Ok(())
+}
+
+#[test]
+fn dies_bad_negative_lines() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "-x1", EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal line count -- -x1"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn twelve_c_suffix() -> Result<()> {
+    run(&[TWELVE, "-c", "1K"], TWELVE)?;
+    run(&[TWELVE, "-c", "1KiB"], TWELVE)?;
+    run(&[TWELVE, "-c", "1b"], TWELVE)?;
+    run(&[TWELVE, "-n", "1k"], TWELVE)
+}
+
+#[test]
+fn twelve_c_minus_suffix() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "-1KB", TWELVE])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+#[test]
+fn dies_bytes_overflow() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "99999999999999999999", EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "illegal byte count -- 99999999999999999999: value too large",
+        ));
+    Command::cargo_bin(PRG)?
+        .args(["-c", "20E", EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "illegal byte count -- 20E: value too large",
+        ));
+    Ok(())
+}
+
+#[test]
+fn dies_bad_suffix() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "4Q", EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal byte count -- 4Q"));
+    Command::cargo_bin(PRG)?
+        .args(["-n", "2KiBs", EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal line count -- 2KiBs"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn all_quiet() -> Result<()> {
+    let expected = format!("{}{}", fs::read_to_string(ONE)?, fs::read_to_string(TWO)?);
+    for flag in ["-q", "--quiet", "--silent"] {
+        Command::cargo_bin(PRG)?
+            .args([flag, ONE, TWO])
+            .assert()
+            .success()
+            .stdout(expected.clone());
+    }
+    Ok(())
+}
+
+#[test]
+fn one_verbose() -> Result<()> {
+    let expected = format!("==> {ONE} <==\n{}", fs::read_to_string(ONE)?);
+    Command::cargo_bin(PRG)?
+        .args(["-v", ONE])
+        .assert()
+        .success()
+        .stdout(expected.clone());
+
+    // 後に指定したほうが優先される
+    Command::cargo_bin(PRG)?
+        .args(["-q", "-v", ONE])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+#[test]
+fn zero_terminated_stdin() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-z", "-n", "2"])
+        .write_stdin("a\nb\0c\0d\0")
+        .assert()
+        .success()
+        .stdout("a\nb\0c\0");
+    Command::cargo_bin(PRG)?
+        .args(["--zero-terminated", "-n", "-1"])
+        .write_stdin("a\0b\0c")
+        .assert()
+        .success()
+        .stdout("a\0b\0");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn one_c1_raw_bytes() -> Result<()> {
+    // 文字の途中で切れても U+FFFD に置き換えない
+    Command::cargo_bin(PRG)?
+        .args(["-c", "1", ONE])
+        .assert()
+        .success()
+        .stdout(predicate::eq(&fs::read(ONE)?[..1]));
+    Command::cargo_bin(PRG)?
+        .args(["-c", "3"])
+        .write_stdin(&b"\xff\xfe\x00\x01"[..])
+        .assert()
+        .success()
+        .stdout(predicate::eq(&b"\xff\xfe\x00"[..]));
+    Ok(())
+}
+
+#[test]
+fn one_chars() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--chars", "1", ONE])
+        .assert()
+        .success()
+        .stdout("Ö");
+    Command::cargo_bin(PRG)?
+        .args(["--chars", "3"])
+        .write_stdin("日本語のテキスト")
+        .assert()
+        .success()
+        .stdout("日本語");
+    Ok(())
+}
+
+#[test]
+fn dies_chars_and_bytes() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--chars", "1", "-c", "2"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Command::cargo_bin(PRG)?
+        .args(["--chars", "x", EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal character count -- x"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn huge_byte_count() -> Result<()> {
+    // 指定したバイト数の分のバッファは確保しない
+    Command::cargo_bin(PRG)?
+        .args(["-c", "1E", TWELVE])
+        .assert()
+        .success()
+        .stdout(fs::read_to_string(TWELVE)?);
+    Command::cargo_bin(PRG)?
+        .args(["-c", "1E"])
+        .write_stdin(fs::read(TWELVE)?)
+        .assert()
+        .success()
+        .stdout(fs::read_to_string(TWELVE)?);
+    Ok(())
+}
+
+#[test]
+fn twelve_c_minus10() -> Result<()> {
+    let input = fs::read(TWELVE)?;
+    Command::cargo_bin(PRG)?
+        .args(["-c", "-10", TWELVE])
+        .assert()
+        .success()
+        .stdout(predicate::eq(&input[..input.len() - 10]));
+    Command::cargo_bin(PRG)?
+        .args(["-c", "-1K", TWELVE])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(target_os = "linux")]
+fn proc_file_c_minus5() -> Result<()> {
+    // /proc のファイルは大きさが 0 と報告されるので、読んでから末尾を除く
+    let version = fs::read("/proc/version")?;
+    Command::cargo_bin(PRG)?
+        .args(["-c", "-5", "/proc/version"])
+        .assert()
+        .success()
+        .stdout(predicate::eq(&version[..version.len() - 5]));
+    Command::cargo_bin(PRG)?
+        .args(["-c", "5", "/proc/version"])
+        .assert()
+        .success()
+        .stdout(predicate::eq(&version[..5]));
+    Ok(())
+}
@@ -0,0 +1,193 @@
+use clap::{CommandFactory, ValueEnum};
+use std::io::{self, Write};
+
+/**
+ * 補完スクリプトを出力できるシェル
+ */
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl From<Shell> for clap_complete::Shell {
+    fn from(shell: Shell) -> clap_complete::Shell {
+        match shell {
+            Shell::Bash => clap_complete::Shell::Bash,
+            Shell::Zsh => clap_complete::Shell::Zsh,
+            Shell::Fish => clap_complete::Shell::Fish,
+        }
+    }
+}
+
+/**
+ * GenerateArgs のオプションの ID
+ *
+ * 必須の位置引数には required_unless_present_any = GENERATE_IDS を指定する
+ */
+pub const GENERATE_IDS: [&str; 2] = ["generate_completions", "generate_man"];
+
+// 各コマンドに共通のオプション (--generate-completions, --generate-man)
+//
+// コマンドの Args に #[command(flatten)] で追加する。
+// サブコマンドにすると "man" などの名前のファイルや文字列を引数に渡せなくなるため、長いオプションにしている。
+// ドキュメントコメントにすると、追加先のコマンドの説明 (long_about) が置き換わるので使わない
+#[derive(Debug, Default, clap::Args)]
+pub struct GenerateArgs {
+    /// Print a shell completion script
+    #[arg(
+        id = "generate_completions",
+        long = "generate-completions",
+        value_name = "SHELL",
+        value_enum,
+        exclusive = true
+    )]
+    completions: Option<Shell>,
+
+    /// Print the man page in roff format
+    #[arg(id = "generate_man", long = "generate-man", exclusive = true)]
+    man: bool,
+}
+
+impl GenerateArgs {
+    /**
+     * 指定された生成内容 (どちらも指定されていない場合は None)
+     */
+    pub fn requested(&self) -> Option<Generate> {
+        match (self.completions, self.man) {
+            (Some(shell), _) => Some(Generate::Completions { shell }),
+            (None, true) => Some(Generate::Man),
+            (None, false) => None,
+        }
+    }
+
+    /**
+     * 指定されていれば、標準出力に書き出して終了する
+     */
+    pub fn exit_if_requested<C: CommandFactory>(&self) {
+        if let Some(generate) = self.requested() {
+            generate.exit::<C>();
+        }
+    }
+}
+
+/**
+ * 補完スクリプトか man ページか
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Generate {
+    Completions { shell: Shell },
+    Man,
+}
+
+impl Generate {
+    /**
+     * C のコマンド定義から補完スクリプトや man ページを生成して書き出す
+     */
+    pub fn write<C: CommandFactory>(&self, out: &mut dyn Write) -> io::Result<()> {
+        let mut cmd = C::command();
+        match self {
+            Generate::Completions { shell } => {
+                // clap_complete は書き込みに失敗すると panic するので、一度バッファに書き出す
+                let name = cmd.get_name().to_string();
+                let mut script = vec![];
+                clap_complete::generate(
+                    clap_complete::Shell::from(*shell),
+                    &mut cmd,
+                    name,
+                    &mut script,
+                );
+                out.write_all(&script)
+            }
+            Generate::Man => clap_mangen::Man::new(cmd).render(out),
+        }
+    }
+
+    /**
+     * 標準出力に書き出して終了する (--help と同じ扱い)
+     */
+    pub fn exit<C: CommandFactory>(&self) -> ! {
+        let prog = C::command().get_name().to_string();
+        match self.write::<C>(&mut io::stdout().lock()) {
+            // head などに渡して途中で閉じられた場合は失敗としない
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+                eprintln!("{}: {}", prog, e);
+                std::process::exit(1);
+            }
+            _ => {}
+        }
+        std::process::exit(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Generate, GenerateArgs, Shell, GENERATE_IDS};
+    use clap::{CommandFactory, Parser};
+
+    #[derive(Debug, Parser)]
+    #[command(name = "demo", about = "Demo command")]
+    struct Args {
+        #[command(flatten)]
+        generate: GenerateArgs,
+
+        /// Input file
+        #[arg(value_name = "FILE", required_unless_present_any = GENERATE_IDS)]
+        file: Option<String>,
+
+        /// Enable the flag
+        #[arg(short = 'x', long = "flag")]
+        flag: bool,
+    }
+
+    fn render(generate: Generate) -> String {
+        let mut out = vec![];
+        generate.write::<Args>(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_completions() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let script = render(Generate::Completions { shell });
+            assert!(script.contains("demo"));
+            assert!(script.contains("flag"));
+        }
+    }
+
+    #[test]
+    fn test_man() {
+        let page = render(Generate::Man);
+        assert!(page.starts_with(".ie \\n(.g"));
+        assert!(page.contains(".TH demo 1"));
+        assert!(page.contains("Demo command"));
+    }
+
+    #[test]
+    fn test_requested() {
+        let args = Args::parse_from(["demo", "--generate-completions", "zsh"]);
+        assert_eq!(
+            args.generate.requested(),
+            Some(Generate::Completions { shell: Shell::Zsh })
+        );
+        let args = Args::parse_from(["demo", "--generate-man"]);
+        assert_eq!(args.generate.requested(), Some(Generate::Man));
+
+        // 位置引数の "man" はそのまま渡る
+        let args = Args::parse_from(["demo", "man"]);
+        assert_eq!(args.generate.requested(), None);
+        assert_eq!(args.file.as_deref(), Some("man"));
+
+        // 他の引数と一緒には使えない
+        assert!(Args::try_parse_from(["demo", "--generate-man", "man"]).is_err());
+
+        // コマンドの説明はそのまま
+        assert_eq!(Args::command().get_long_about(), None);
+
+        // --help に表示する
+        let help = Args::command().render_help().to_string();
+        assert!(help.contains("--generate-completions <SHELL>"));
+        assert!(help.contains("--generate-man"));
+    }
+}
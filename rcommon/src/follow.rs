@@ -0,0 +1,233 @@
+use crate::Reporter;
+use clap::ValueEnum;
+use std::fs::{self, File, Metadata};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::thread;
+use std::time::Duration;
+
+// 追記を確認する間隔
+const INTERVAL: Duration = Duration::from_millis(100);
+
+/**
+ * 追記を監視する方法
+ */
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Follow {
+    Descriptor, // 開いたファイルを監視し続ける (名前が変わっても同じファイルを読む)
+    Name,       // ファイル名で監視し、ローテーションで置き換わったら開き直す
+}
+
+/**
+ * --follow で監視しているファイル
+ *
+ * Follow::Name の場合は、ローテーションで同じ名前の別のファイルに置き換わったら開き直す
+ */
+pub struct Followed {
+    filename: String,
+    file: Option<File>, // 開けていない場合は None (--retry で開き直す)
+    pos: u64,           // 出力済みのバイト数
+    how: Follow,
+    last_error: Option<io::ErrorKind>, // 最後に表示したエラーの種類 (同じエラーは繰り返し表示しない)
+}
+
+impl Followed {
+    /**
+     * 出力し終えたファイルの監視を始める
+     *
+     * ファイルの現在位置までを出力済みとみなす
+     */
+    pub fn new(filename: &str, mut file: File, how: Follow) -> io::Result<Followed> {
+        Ok(Followed {
+            filename: filename.to_string(),
+            pos: file.stream_position()?,
+            file: Some(file),
+            how,
+            last_error: None,
+        })
+    }
+
+    /**
+     * 開けなかったファイルを、開けるようになるまで監視する (--retry)
+     *
+     * err は表示済みのエラーで、同じ種類のエラーが続く間は表示しない
+     */
+    pub fn failed(filename: &str, err: &io::Error, how: Follow) -> Followed {
+        Followed {
+            filename: filename.to_string(),
+            file: None,
+            pos: 0,
+            how,
+            last_error: Some(err.kind()),
+        }
+    }
+
+    /**
+     * 追記された分を出力し、切り詰めや置き換えを検出する
+     *
+     * 前回と別のファイルの分を出力する場合は、先に "==> file <==" を出力する。
+     * 監視を続けられない場合は Err を返す
+     */
+    fn poll<W: Write + ?Sized>(
+        &mut self,
+        out: &mut W,
+        last_shown: &mut Option<String>,
+        reporter: &mut Reporter,
+    ) -> io::Result<()> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => {
+                self.file = Some(File::open(&self.filename)?);
+                self.pos = 0;
+                return self.poll(out, last_shown, reporter);
+            }
+        };
+
+        // 前回より小さくなっていれば、切り詰められたとみなして先頭から読み直す
+        let len = file.metadata()?.len();
+        if len < self.pos {
+            reporter.note(&self.filename, "file truncated");
+            file.seek(SeekFrom::Start(0))?;
+            self.pos = 0;
+        }
+        if len > self.pos {
+            if let Some(last) = last_shown.as_mut().filter(|last| **last != self.filename) {
+                write_header(out, &self.filename, last.is_empty())?;
+                last.clone_from(&self.filename);
+            }
+            self.pos += io::copy(file, out)?;
+            out.flush()?;
+        }
+
+        // 同じ名前で別のファイルが作られていれば、古いファイルを読み切ってから開き直す
+        if self.how == Follow::Name {
+            if let Ok(meta) = fs::metadata(&self.filename) {
+                if file_id(&meta) != file_id(&file.metadata()?) {
+                    reporter.note(&self.filename, "file replaced; following new file");
+                    self.file = None;
+                    return self.poll(out, last_shown, reporter);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/**
+ * 追記されたバイト列を、監視できるファイルがなくなるまで出力し続ける
+ *
+ * headers が true の場合は、last_shown (最後に表示したファイル名) と別のファイルの分を
+ * 出力するたびに "==> file <==" を出力する
+ */
+pub fn follow<W: Write + ?Sized>(
+    mut followed: Vec<Followed>,
+    retry: bool,
+    headers: bool,
+    last_shown: Option<String>,
+    out: &mut W,
+    reporter: &mut Reporter,
+) -> io::Result<()> {
+    // ヘッダーを出力しない場合は None、まだ何も表示していない場合は空文字列
+    let mut last_shown = headers.then(|| last_shown.unwrap_or_default());
+    while !followed.is_empty() {
+        thread::sleep(INTERVAL);
+        poll_all(&mut followed, retry, &mut last_shown, out, reporter);
+    }
+    Ok(())
+}
+
+/**
+ * 全てのファイルを1回ずつ確かめ、監視を続けられないものを取り除く
+ *
+ * --retry の場合は、開けるようになるまで試し続ける。
+ * GNU tail と同じく、エラーは前回と種類が変わったときだけ表示する
+ */
+fn poll_all<W: Write + ?Sized>(
+    followed: &mut Vec<Followed>,
+    retry: bool,
+    last_shown: &mut Option<String>,
+    out: &mut W,
+    reporter: &mut Reporter,
+) {
+    followed.retain_mut(|entry| match entry.poll(out, last_shown, reporter) {
+        Ok(_) => {
+            entry.last_error = None;
+            true
+        }
+        Err(err) => {
+            if !retry || entry.last_error != Some(err.kind()) {
+                reporter.error(&entry.filename, &err);
+            }
+            entry.last_error = Some(err.kind());
+            entry.file = None;
+            retry
+        }
+    });
+}
+
+/**
+ * "==> file <==" を出力する (最初のファイル以外は空行を挟む)
+ */
+pub fn write_header<W: Write + ?Sized>(out: &mut W, filename: &str, first: bool) -> io::Result<()> {
+    writeln!(out, "{}==> {} <==", if first { "" } else { "\n" }, filename)
+}
+
+/**
+ * ファイルを識別するための (デバイス番号, inode番号)
+ */
+#[cfg(unix)]
+fn file_id(meta: &Metadata) -> (u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+    (meta.dev(), meta.ino())
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &Metadata) -> (u64, u64) {
+    (0, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{poll_all, Follow, Followed};
+    use crate::Reporter;
+    use std::fs::{self, File};
+    use std::io;
+
+    #[test]
+    fn test_retry_reports_once() {
+        // 開けても読めないディレクトリは、何度確かめてもエラーを1回だけ表示する
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+        fs::create_dir(&path).unwrap();
+        let filename = path.to_str().unwrap();
+        let err = io::Error::from(io::ErrorKind::NotFound);
+        let mut followed = vec![Followed::failed(filename, &err, Follow::Name)];
+
+        let mut out = vec![];
+        let mut err = vec![];
+        let mut reporter = Reporter::new("tailr", &mut err);
+        for _ in 0..5 {
+            poll_all(&mut followed, true, &mut None, &mut out, &mut reporter);
+        }
+        assert_eq!(followed.len(), 1);
+
+        // 読めるファイルに置き換われば、続きを出力する
+        fs::remove_dir(&path).unwrap();
+        fs::write(&path, "one\n").unwrap();
+        poll_all(&mut followed, true, &mut None, &mut out, &mut reporter);
+        assert_eq!(out, b"one\n");
+        drop(reporter);
+        let err = String::from_utf8(err).unwrap();
+        assert_eq!(err.lines().count(), 1, "{}", err);
+        assert!(err.starts_with(&format!("tailr: {}: ", filename)));
+
+        // --retry でなければ、エラーになったファイルは監視しない
+        let file = File::open(&path).unwrap();
+        let mut followed = vec![Followed::new(filename, file, Follow::Name).unwrap()];
+        fs::remove_file(&path).unwrap();
+        fs::create_dir(&path).unwrap();
+        let mut err = vec![];
+        let mut reporter = Reporter::new("tailr", &mut err);
+        poll_all(&mut followed, false, &mut None, &mut out, &mut reporter);
+        assert!(followed.is_empty());
+    }
+}
@@ -0,0 +1,276 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+pub mod cli;
+pub mod follow;
+pub mod size;
+// 結合テストで使う補助関数 (dev-dependencies で feature = "testing" を指定する)
+#[cfg(feature = "testing")]
+pub mod testing;
+
+pub type MyResult<T> = Result<T, Box<dyn Error>>;
+
+// 読み込みに使うバッファのサイズ
+pub const BUF_SIZE: usize = 128 * 1024;
+
+/**
+ * 入力元
+ *
+ * 変換なしでコピーするときに具体的な型が必要になるので、Box<dyn BufRead> にはしない
+ */
+pub enum Input<'a> {
+    Stdin(&'a mut dyn BufRead), // "-" で読む入力 (呼び出し側から渡される)
+    File(File),
+    Reader(Box<dyn BufRead + 'a>), // 展開などの変換を挟んだ入力
+}
+
+impl<'a> Input<'a> {
+    /**
+     * ファイル名が "-" の場合は stdin、それ以外の場合はファイルを開く
+     *
+     * ディレクトリは開けても読めないので、開く時点でエラーにする
+     */
+    pub fn open(filename: impl AsRef<Path>, stdin: &'a mut dyn BufRead) -> io::Result<Input<'a>> {
+        let filename = filename.as_ref();
+        if filename.as_os_str() == "-" {
+            return Ok(Input::Stdin(stdin));
+        }
+        let file = File::open(filename)?;
+        if file.metadata()?.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::IsADirectory,
+                "Is a directory",
+            ));
+        }
+        Ok(Input::File(file))
+    }
+
+    /**
+     * 行単位で読むためのバッファ付きリーダーを返す
+     */
+    pub fn into_reader(self) -> Box<dyn BufRead + 'a> {
+        match self {
+            Input::Stdin(stdin) => Box::new(stdin),
+            Input::File(file) => Box::new(BufReader::with_capacity(BUF_SIZE, file)),
+            Input::Reader(reader) => reader,
+        }
+    }
+
+    /**
+     * 変換せずにそのまま出力へコピーする
+     *
+     * std::io::copy は Linux では File や標準入出力同士のコピーに
+     * copy_file_range / splice / sendfile を使うので、カーネル内でコピーされる
+     */
+    pub fn copy_to<W: Write + ?Sized>(self, out: &mut W) -> io::Result<u64> {
+        match self {
+            Input::Stdin(stdin) => io::copy(stdin, out),
+            Input::File(mut file) => io::copy(&mut file, out),
+            Input::Reader(mut reader) => io::copy(&mut reader, out),
+        }
+    }
+
+    /**
+     * 展開などの変換を挟む
+     *
+     * hook には生のバイト列を読むリーダーが渡される
+     */
+    pub fn decode_with<F>(self, hook: F) -> io::Result<Input<'a>>
+    where
+        F: FnOnce(Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>>,
+    {
+        let reader: Box<dyn Read + 'a> = match self {
+            Input::Stdin(stdin) => Box::new(stdin),
+            Input::File(file) => Box::new(file),
+            Input::Reader(reader) => Box::new(reader),
+        };
+        let decoded = hook(reader)?;
+        Ok(Input::Reader(Box::new(BufReader::with_capacity(
+            BUF_SIZE, decoded,
+        ))))
+    }
+}
+
+/**
+ * ファイルを開いて、行単位で読むためのリーダーを返す
+ */
+pub fn open<'a>(
+    filename: &str,
+    stdin: &'a mut dyn BufRead,
+) -> MyResult<Box<dyn BufRead + 'a>> {
+    Ok(Input::open(filename, stdin)?.into_reader())
+}
+
+/**
+ * 個々のファイルのエラーを報告済みであることを示すエラー
+ *
+ * main では何も表示せず、終了コードだけを非0にする
+ */
+#[derive(Debug)]
+pub struct Reported;
+
+impl fmt::Display for Reported {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "one or more inputs failed")
+    }
+}
+
+impl Error for Reported {}
+
+/**
+ * "prog: file: error" の形式でエラーを表示し、失敗があったかどうかを記録する
+ */
+pub struct Reporter<'a> {
+    prog: String,
+    failed: bool,
+    err: &'a mut dyn Write, // エラーの出力先 (通常は標準エラー)
+}
+
+impl<'a> Reporter<'a> {
+    pub fn new(prog: &str, err: &'a mut dyn Write) -> Reporter<'a> {
+        Reporter {
+            prog: prog.to_string(),
+            failed: false,
+            err,
+        }
+    }
+
+    /**
+     * ファイルごとのエラーを表示する (処理は次のファイルへ続ける)
+     */
+    pub fn error(&mut self, filename: &str, err: impl fmt::Display) {
+        self.note(filename, err);
+        self.failed = true;
+    }
+
+    /**
+     * 終了コードに影響しないメッセージを表示する
+     */
+    pub fn note(&mut self, filename: &str, msg: impl fmt::Display) {
+        // 標準エラーに書けない場合は報告のしようがないので無視する
+        let _ = writeln!(self.err, "{}", diagnostic(&self.prog, filename, msg));
+    }
+
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    /**
+     * エラーがあった場合は Reported を返す
+     */
+    pub fn finish(self) -> MyResult<()> {
+        if self.failed {
+            Err(Box::new(Reported))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/**
+ * "prog: file: error" 形式のメッセージを作る
+ */
+pub fn diagnostic(prog: &str, filename: &str, msg: impl fmt::Display) -> String {
+    format!("{}: {}: {}", prog, filename, msg)
+}
+
+/**
+ * main から呼び出す
+ *
+ * エラーがあれば "prog: error" の形式で表示し(報告済みの場合は表示しない)、終了コード1で終了する。
+ * 引数のエラー (clap::Error) は、clap が解析に失敗したときと同じ形式・終了コードにする
+ */
+pub fn exit_on_error(prog: &str, result: MyResult<()>) {
+    if let Err(e) = result {
+        if let Some(e) = e.downcast_ref::<clap::Error>() {
+            e.exit();
+        }
+        if !e.is::<Reported>() {
+            eprintln!("{}: {}", prog, e);
+        }
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diagnostic, open, Input, Reporter};
+    use std::io::{self, Cursor, ErrorKind, Read};
+
+    #[test]
+    fn test_open_file() {
+        let mut contents = String::new();
+        open("Cargo.toml", &mut io::empty())
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert!(contents.contains("rcommon"));
+    }
+
+    #[test]
+    fn test_open_missing() {
+        let err = Input::open("no-such-file", &mut io::empty()).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_open_directory() {
+        let err = Input::open("src", &mut io::empty()).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::IsADirectory);
+        assert_eq!(err.to_string(), "Is a directory");
+    }
+
+    #[test]
+    fn test_decode_with() {
+        let mut stdin = io::empty();
+        let input = Input::open("Cargo.toml", &mut stdin)
+            .unwrap()
+            .decode_with(|mut reader| {
+                let mut buf = vec![];
+                reader.read_to_end(&mut buf)?;
+                buf.make_ascii_uppercase();
+                Ok(Box::new(Cursor::new(buf)))
+            })
+            .unwrap();
+        let mut out = vec![];
+        input.copy_to(&mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("RCOMMON"));
+    }
+
+    #[test]
+    fn test_diagnostic() {
+        assert_eq!(
+            diagnostic("catr", "foo.txt", "Is a directory"),
+            "catr: foo.txt: Is a directory"
+        );
+    }
+
+    #[test]
+    fn test_open_stdin() {
+        let mut stdin = Cursor::new("from stdin");
+        let mut contents = String::new();
+        open("-", &mut stdin)
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "from stdin");
+    }
+
+    #[test]
+    fn test_reporter() {
+        let mut err = vec![];
+        let reporter = Reporter::new("catr", &mut err);
+        assert!(!reporter.failed());
+        assert!(reporter.finish().is_ok());
+        assert!(err.is_empty());
+
+        let mut reporter = Reporter::new("catr", &mut err);
+        reporter.error("foo.txt", "Is a directory");
+        assert!(reporter.failed());
+        assert!(reporter.finish().is_err());
+        assert_eq!(err, b"catr: foo.txt: Is a directory\n");
+    }
+}
@@ -0,0 +1,97 @@
+/**
+ * 数の変換に失敗した理由
+ */
+#[derive(Debug, PartialEq)]
+pub enum SizeError {
+    Invalid,  // 数でない、または知らない単位
+    TooLarge, // usize に収まらない
+}
+
+impl SizeError {
+    /**
+     * "illegal byte count -- " などのあとに続けるメッセージにする
+     */
+    pub fn describe(&self, val: &str) -> Box<dyn std::error::Error> {
+        match self {
+            SizeError::Invalid => From::from(val),
+            SizeError::TooLarge => From::from(format!("{}: value too large", val)),
+        }
+    }
+}
+
+/**
+ * 単位付きの数を変換する
+ *
+ * GNU head や tail と同じく、b は 512、K・KiB は 1024、KB は 1000 倍
+ * (M・G・T・P・E も同様、k と m は K と M と同じ)
+ */
+pub fn parse_size(val: &str) -> Result<usize, SizeError> {
+    let digits = val.find(|c: char| !c.is_ascii_digit()).unwrap_or(val.len());
+    let (num, suffix) = val.split_at(digits);
+    if num.is_empty() {
+        return Err(SizeError::Invalid);
+    }
+    let multiplier = multiplier(suffix).ok_or(SizeError::Invalid)?;
+    // 数字だけの並びが変換できないのは、桁が多すぎる場合だけ
+    let num: usize = num.parse().map_err(|_| SizeError::TooLarge)?;
+    num.checked_mul(multiplier).ok_or(SizeError::TooLarge)
+}
+
+/**
+ * 単位の倍率 (知らない単位の場合や、倍率が usize に収まらない場合は None)
+ */
+fn multiplier(suffix: &str) -> Option<usize> {
+    if suffix.is_empty() {
+        return Some(1);
+    }
+    if suffix == "b" {
+        return Some(512);
+    }
+    let mut chars = suffix.chars();
+    let power = match chars.next()? {
+        'K' | 'k' => 1,
+        'M' | 'm' => 2,
+        'G' => 3,
+        'T' => 4,
+        'P' => 5,
+        'E' => 6,
+        _ => return None,
+    };
+    let base: usize = match chars.as_str() {
+        "" | "iB" => 1024,
+        "B" => 1000,
+        _ => return None,
+    };
+    base.checked_pow(power)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_size, SizeError};
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("10"), Ok(10));
+        assert_eq!(parse_size("2b"), Ok(1024));
+        assert_eq!(parse_size("4K"), Ok(4096));
+        assert_eq!(parse_size("4k"), Ok(4096));
+        assert_eq!(parse_size("4KiB"), Ok(4096));
+        assert_eq!(parse_size("4KB"), Ok(4000));
+        assert_eq!(parse_size("10M"), Ok(10 * 1024 * 1024));
+        assert_eq!(parse_size("3MB"), Ok(3_000_000));
+        assert_eq!(parse_size("1GiB"), Ok(1 << 30));
+        assert_eq!(parse_size("0K"), Ok(0));
+
+        assert_eq!(parse_size(""), Err(SizeError::Invalid));
+        assert_eq!(parse_size("K"), Err(SizeError::Invalid));
+        assert_eq!(parse_size("4X"), Err(SizeError::Invalid));
+        assert_eq!(parse_size("4Kib"), Err(SizeError::Invalid));
+        assert_eq!(parse_size("4 K"), Err(SizeError::Invalid));
+        assert_eq!(parse_size("-4"), Err(SizeError::Invalid));
+
+        // 桁あふれ
+        assert_eq!(parse_size("99999999999999999999"), Err(SizeError::TooLarge));
+        assert_eq!(parse_size("16E"), Err(SizeError::TooLarge));
+        assert_eq!(parse_size("99999999999999999999X"), Err(SizeError::Invalid));
+    }
+}
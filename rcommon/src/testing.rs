@@ -0,0 +1,50 @@
+use std::fs::OpenOptions;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/**
+ * --follow のプロセスを起動し、標準出力をスレッドで読み続ける
+ *
+ * 読んだバイト列は、受け取った順に Receiver に届く
+ */
+pub fn spawn_follow(mut cmd: Command) -> io::Result<(Child, Receiver<Vec<u8>>)> {
+    let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::null()).spawn()?;
+    let mut stdout = child.stdout.take().unwrap();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = [0; 1024];
+        while let Ok(n) = stdout.read(&mut buf) {
+            if n == 0 || tx.send(buf[..n].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+    Ok((child, rx))
+}
+
+/**
+ * 期待する出力が揃うまで (最長10秒) 待つ
+ */
+pub fn wait_for(rx: &Receiver<Vec<u8>>, output: &mut Vec<u8>, expected: &str) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while output != expected.as_bytes() {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match rx.recv_timeout(timeout) {
+            Ok(bytes) => output.extend(bytes),
+            Err(_) => break,
+        }
+    }
+    assert_eq!(String::from_utf8_lossy(output), expected);
+}
+
+/**
+ * ファイルの末尾に追記する (ない場合は作る)
+ */
+pub fn append(path: &Path, text: &str) -> io::Result<()> {
+    let mut file = OpenOptions::new().append(true).create(true).open(path)?;
+    file.write_all(text.as_bytes())
+}
@@ -0,0 +1,157 @@
+use rcommon::{MyResult, Reporter};
+use std::env;
+use std::ffi::OsString;
+use std::path::Path;
+
+/**
+ * 1つのバイナリにまとめたコマンド (アプレット)
+ */
+struct Applet {
+    name: &'static str, // シンボリックリンクやサブコマンドに使う名前
+    prog: &'static str, // 元のコマンド名 (こちらでも呼び出せる)
+    main: fn(Vec<OsString>) -> MyResult<()>,
+}
+
+const APPLETS: &[Applet] = &[
+    Applet { name: "cat", prog: "catr", main: cat },
+    Applet { name: "cut", prog: "cutr", main: cut },
+    Applet { name: "echo", prog: "echor", main: echo },
+    Applet { name: "false", prog: "false", main: false_ },
+    Applet { name: "find", prog: "findr", main: find },
+    Applet { name: "head", prog: "headr", main: head },
+    Applet { name: "tail", prog: "tailr", main: tail },
+    Applet { name: "true", prog: "true", main: true_ },
+    Applet { name: "uniq", prog: "uniqr", main: uniq },
+    Applet { name: "wc", prog: "wcr", main: wc },
+];
+
+fn cat(args: Vec<OsString>) -> MyResult<()> {
+    catr::get_args_from(args).and_then(catr::run)
+}
+
+fn cut(args: Vec<OsString>) -> MyResult<()> {
+    cutr::get_args_from(args).and_then(cutr::run)
+}
+
+fn echo(args: Vec<OsString>) -> MyResult<()> {
+    echor::get_args_from(args).and_then(echor::run)
+}
+
+fn false_(_args: Vec<OsString>) -> MyResult<()> {
+    std::process::exit(1);
+}
+
+fn find(args: Vec<OsString>) -> MyResult<()> {
+    findr::get_args_from(args).and_then(findr::run)
+}
+
+fn head(args: Vec<OsString>) -> MyResult<()> {
+    headr::get_args_from(args).and_then(headr::run)
+}
+
+fn tail(args: Vec<OsString>) -> MyResult<()> {
+    tailr::get_args_from(args).and_then(tailr::run)
+}
+
+fn true_(_args: Vec<OsString>) -> MyResult<()> {
+    std::process::exit(0);
+}
+
+fn uniq(args: Vec<OsString>) -> MyResult<()> {
+    uniqr::get_args_from(args).and_then(uniqr::run)
+}
+
+fn wc(args: Vec<OsString>) -> MyResult<()> {
+    wcr::get_args_from(args).and_then(wcr::run)
+}
+
+/**
+ * アプレット名 ("cat") か元のコマンド名 ("catr") から探す
+ */
+fn find_applet(name: &str) -> Option<&'static Applet> {
+    APPLETS
+        .iter()
+        .find(|applet| applet.name == name || applet.prog == name)
+}
+
+fn usage() -> String {
+    let names: Vec<_> = APPLETS.iter().map(|applet| applet.name).collect();
+    format!(
+        "Usage: rutils APPLET [ARGS]...\n       \
+         rutils --install DIR\n\n\
+         Applets: {}",
+        names.join(", ")
+    )
+}
+
+/**
+ * DIR に各アプレット名でこのバイナリへのシンボリックリンクを作る
+ */
+fn install(dir: &Path) -> MyResult<()> {
+    let exe = env::current_exe()?;
+    let mut stderr = std::io::stderr();
+    let mut reporter = Reporter::new("rutils", &mut stderr);
+    for applet in APPLETS {
+        let link = dir.join(applet.name);
+        if let Err(err) = symlink(&exe, &link) {
+            reporter.error(&link.display().to_string(), err);
+        }
+    }
+    reporter.finish()
+}
+
+#[cfg(unix)]
+fn symlink(original: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(not(unix))]
+fn symlink(_original: &Path, _link: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "symbolic links are not supported on this platform",
+    ))
+}
+
+fn main() {
+    let mut args: Vec<OsString> = env::args_os().collect();
+
+    // シンボリックリンク経由で呼ばれた場合は argv[0] のファイル名で選ぶ
+    let argv0 = args
+        .first()
+        .and_then(|arg| Path::new(arg).file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if let Some(applet) = find_applet(&argv0) {
+        rcommon::exit_on_error(applet.prog, (applet.main)(args));
+        return;
+    }
+
+    // それ以外の場合は最初の引数をサブコマンドとして扱う
+    let first = args.get(1).map(|arg| arg.to_string_lossy().to_string());
+    match first.as_deref() {
+        Some("-h") | Some("--help") => println!("{}", usage()),
+        Some("--install") => match args.get(2) {
+            Some(dir) => rcommon::exit_on_error("rutils", install(Path::new(dir))),
+            None => {
+                eprintln!("rutils: --install requires a directory\n\n{}", usage());
+                std::process::exit(1);
+            }
+        },
+        Some(name) => match find_applet(name) {
+            Some(applet) => {
+                // アプレットからはサブコマンド名が argv[0] に見えるようにする
+                args.remove(0);
+                rcommon::exit_on_error(applet.prog, (applet.main)(args));
+            }
+            None => {
+                eprintln!("rutils: {}: applet not found\n\n{}", name, usage());
+                std::process::exit(1);
+            }
+        },
+        None => {
+            eprintln!("{}", usage());
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use anyhow::Result;
+use assert_cmd::Command;
+use predicates::prelude::*;
+use pretty_assertions::assert_eq;
+use std::fs;
+use tempfile::tempdir;
+
+const PRG: &str = "rutils";
+const FOX: &str = "../catr/tests/inputs/fox.txt";
+
+// --------------------------------------------------
+#[test]
+fn usage() -> Result<()> {
+    for flag in &["-h", "--help"] {
+        Command::cargo_bin(PRG)?
+            .arg(flag)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("Usage"));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_no_args() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Applets: cat, cut, echo"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_unknown_applet() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .arg("ls")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("rutils: ls: applet not found"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn cat() -> Result<()> {
+    let expected = fs::read_to_string("../catr/tests/expected/fox.txt.n.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["cat", "-n", FOX])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn original_name() -> Result<()> {
+    let expected = fs::read_to_string("../wcr/tests/expected/atlamal.txt.stdin.out")?;
+    Command::cargo_bin(PRG)?
+        .arg("wcr")
+        .write_stdin(fs::read_to_string("../wcr/tests/inputs/atlamal.txt")?)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn echo() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["echo", "-n", "Hello", "there"])
+        .assert()
+        .success()
+        .stdout("Hello there");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn true_false() -> Result<()> {
+    Command::cargo_bin(PRG)?.arg("true").assert().success();
+    Command::cargo_bin(PRG)?.arg("false").assert().failure();
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn applet_error_prefix() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["head", "no-such-file"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::starts_with("headr: no-such-file: "));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn install() -> Result<()> {
+    let dir = tempdir()?;
+    Command::cargo_bin(PRG)?
+        .args(["--install", dir.path().to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut names: Vec<_> = fs::read_dir(dir.path())?
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(
+        names,
+        ["cat", "cut", "echo", "false", "find", "head", "tail", "true", "uniq", "wc"]
+    );
+
+    // シンボリックリンクの名前で呼び出すと、そのアプレットとして動く
+    let expected = fs::read_to_string("../catr/tests/expected/fox.txt.out")?;
+    let output = std::process::Command::new(dir.path().join("cat"))
+        .arg(FOX)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout)?, expected);
+
+    // すでにリンクがある場合はエラー
+    Command::cargo_bin(PRG)?
+        .args(["--install", dir.path().to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("/cat: "));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_install_without_dir() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .arg("--install")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--install requires a directory"));
+    Ok(())
+}
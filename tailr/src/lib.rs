@@ -0,0 +1,546 @@
+use clap::Parser;
+use rcommon::cli::GenerateArgs;
+use rcommon::follow::{self, write_header, Followed};
+use rcommon::size::parse_size;
+use rcommon::{Input, MyResult, Reporter, BUF_SIZE};
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+
+pub use rcommon::follow::Follow;
+
+/**
+ * 出力する行数・バイト数
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Count {
+    Last(usize), // 末尾の K 個 (-n K)
+    From(usize), // K 個目から最後まで (-n +K、+0 は +1 と同じ)
+}
+
+impl From<usize> for Count {
+    fn from(n: usize) -> Count {
+        Count::Last(n)
+    }
+}
+
+#[derive(Debug)]
+pub struct Config {
+    files: Vec<String>,
+    lines: Count,
+    bytes: Option<Count>,
+    headers: Option<bool>, // ファイル名を表示するかどうか (None の場合は複数のファイルのときだけ)
+    follow: Option<Follow>, // 最後まで出力したあとも追記を出力し続ける場合の監視方法
+    retry: bool,           // 開けないファイルを開けるようになるまで試し続けるかどうか
+}
+
+impl Config {
+    /**
+     * 各ファイルの末尾10行を出力する設定を作る
+     */
+    pub fn new(files: Vec<String>) -> Config {
+        Config {
+            files,
+            lines: Count::Last(10),
+            bytes: None,
+            headers: None,
+            follow: None,
+            retry: false,
+        }
+    }
+
+    /**
+     * 出力する行数 (Count::From の場合は指定した行から最後まで出力する)
+     */
+    pub fn lines(mut self, lines: impl Into<Count>) -> Config {
+        self.lines = lines.into();
+        self
+    }
+
+    /**
+     * 行数の代わりに、出力するバイト数を指定する
+     */
+    pub fn bytes(mut self, bytes: impl Into<Count>) -> Config {
+        self.bytes = Some(bytes.into());
+        self
+    }
+
+    /**
+     * ファイルの数にかかわらず、"==> file <==" を表示する (true) か表示しない (false)
+     */
+    pub fn headers(mut self, headers: bool) -> Config {
+        self.headers = Some(headers);
+        self
+    }
+
+    /**
+     * 最後まで出力したあとも、追記された分を出力し続ける
+     *
+     * 標準入力や通常のファイル以外は監視しない
+     */
+    pub fn follow(mut self, follow: Follow) -> Config {
+        self.follow = Some(follow);
+        self
+    }
+
+    pub fn retry(mut self, retry: bool) -> Config {
+        self.retry = retry;
+        self
+    }
+}
+
+// コマンドライン引数の定義 (ヘルプ・補完スクリプト・manページにも使われる)
+#[derive(Debug, Parser)]
+#[command(
+    name = "tailr",
+    version = "0.1.0",
+    author = "hoge",
+    about = "rust tail command"
+)]
+struct Args {
+    /// Number of lines (with a leading '+', print from line LINES onward;
+    /// suffixes such as K, MB or GiB are allowed)
+    #[arg(
+        short = 'n',
+        long = "lines",
+        value_name = "LINES",
+        default_value = "10",
+        allow_hyphen_values = true
+    )]
+    lines: String,
+
+    /// Number of bytes (with a leading '+', print from byte BYTES onward;
+    /// b = 512, K = KiB = 1024, KB = 1000, and so on for M, G, T, P, E)
+    #[arg(
+        short = 'c',
+        long = "bytes",
+        value_name = "BYTES",
+        conflicts_with = "lines",
+        allow_hyphen_values = true
+    )]
+    bytes: Option<String>,
+
+    /// Never print headers giving file names
+    #[arg(
+        short = 'q',
+        long = "quiet",
+        visible_alias = "silent",
+        overrides_with = "verbose"
+    )]
+    quiet: bool,
+
+    /// Always print headers giving file names
+    #[arg(short = 'v', long = "verbose", overrides_with = "quiet")]
+    verbose: bool,
+
+    /// Output appended data as the file grows
+    #[arg(
+        short = 'f',
+        long = "follow",
+        value_name = "HOW",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "descriptor"
+    )]
+    follow: Option<Follow>,
+
+    /// Same as --follow=name --retry
+    #[arg(short = 'F', conflicts_with = "follow")]
+    follow_name: bool,
+
+    /// Keep trying to open a file until it is accessible (with --follow)
+    #[arg(long = "retry")]
+    retry: bool,
+
+    /// Input file(s)
+    #[arg(value_name = "FILE", default_value = "-")]
+    files: Vec<String>,
+
+    #[command(flatten)]
+    generate: GenerateArgs,
+}
+
+pub fn get_args() -> MyResult<Config> {
+    get_args_from(std::env::args_os())
+}
+
+/**
+ * 指定した引数列を解析し、Config構造体を返す
+ *
+ * 先頭の要素はプログラム名として扱われる
+ */
+pub fn get_args_from<I, T>(args: I) -> MyResult<Config>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let args = Args::parse_from(args);
+    args.generate.exit_if_requested::<Args>();
+
+    // 引数のパース
+    let lines = parse_count(&args.lines).map_err(|e| format!("illegal line count -- {}", e))?;
+
+    let bytes = args
+        .bytes
+        .as_deref()
+        .map(parse_count)
+        .transpose() // Option<Result<T, E>> -> Result<Option<T>, E>
+        .map_err(|e| format!("illegal byte count -- {}", e))?;
+
+    let headers = match (args.quiet, args.verbose) {
+        (true, _) => Some(false),
+        (_, true) => Some(true),
+        _ => None,
+    };
+
+    let follow = if args.follow_name {
+        Some(Follow::Name)
+    } else {
+        args.follow
+    };
+
+    Ok(Config {
+        files: args.files,
+        lines,
+        bytes,
+        headers,
+        follow,
+        retry: args.retry || args.follow_name,
+    })
+}
+
+pub fn run(config: Config) -> MyResult<()> {
+    run_with(
+        config,
+        &mut io::stdin().lock(),
+        &mut io::stdout().lock(),
+        &mut io::stderr(),
+    )
+}
+
+/**
+ * 標準入力・標準出力・標準エラーの代わりに、指定したリーダーとライターを使って実行する
+ *
+ * --follow の場合は、監視できるファイルがなくなるまで戻らない
+ */
+pub fn run_with(
+    config: Config,
+    stdin: &mut dyn BufRead,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> MyResult<()> {
+    let mut reporter = Reporter::new("tailr", err);
+    let headers = config.headers.unwrap_or(config.files.len() > 1);
+    // -n 0 や -c 0 の場合は何も出力しないので、GNU tail と同じくファイル名も表示しない
+    // (--follow で追記されたときに表示する)
+    let nothing = config.bytes.unwrap_or(config.lines) == Count::Last(0);
+    let mut followed = vec![];
+    let mut last_shown = None; // 最後に "==> file <==" を表示したファイル
+
+    for filename in &config.files {
+        let input = match Input::open(filename, stdin) {
+            Ok(input) => input,
+            Err(err) => {
+                reporter.error(filename, &err);
+                // --retry の場合は、あとから作られたファイルも監視する
+                if let (Some(how), true) = (config.follow, config.retry) {
+                    if filename != "-" {
+                        followed.push(Followed::failed(filename, &err, how));
+                    }
+                }
+                continue;
+            }
+        };
+
+        // 複数のファイルが指定されている場合 (または -v の場合) は、ファイル名を表示
+        if headers && !nothing {
+            write_header(out, filename, last_shown.is_none())?;
+            last_shown = Some(filename.to_string());
+        }
+
+        match tail(&config, input, out) {
+            Err(err) => reporter.error(filename, err),
+            Ok(file) => {
+                if let (Some(how), Some(file)) = (config.follow, file) {
+                    followed.push(Followed::new(filename, file, how)?);
+                }
+            }
+        }
+    }
+
+    if !followed.is_empty() {
+        out.flush()?;
+        follow::follow(
+            followed,
+            config.retry,
+            headers,
+            last_shown,
+            out,
+            &mut reporter,
+        )?;
+    }
+    reporter.finish()
+}
+
+/**
+ * 1つの入力の末尾部分を出力する
+ *
+ * 通常のファイルの場合は、--follow で続きを読めるように、最後まで読んだファイルを返す。
+ * /proc のファイルのように大きさが 0 と報告されるものは、先頭から読まないと末尾が分からない
+ */
+fn tail(config: &Config, input: Input, out: &mut dyn Write) -> io::Result<Option<File>> {
+    match input {
+        Input::File(mut file) if file.metadata()?.is_file() => {
+            if file.metadata()?.len() > 0 {
+                tail_file(config, &mut file, out)?;
+                return Ok(Some(file));
+            }
+            tail_reader(config, BufReader::with_capacity(BUF_SIZE, &mut file), out)?;
+            // 本当に空のファイルだった場合だけ、追記を監視する
+            Ok((file.stream_position()? == 0).then_some(file))
+        }
+        input => {
+            tail_reader(config, input.into_reader(), out)?;
+            Ok(None)
+        }
+    }
+}
+
+/**
+ * 通常のファイルの末尾を出力する
+ *
+ * 大きさが分かるので、末尾から数える場合もファイル全体は読まずに、開始位置へシークする
+ */
+fn tail_file(config: &Config, file: &mut File, out: &mut dyn Write) -> io::Result<()> {
+    let len = file.metadata()?.len();
+    let start = match (config.bytes, config.lines) {
+        (Some(Count::Last(num_bytes)), _) => len.saturating_sub(num_bytes as u64),
+        (Some(Count::From(num_bytes)), _) => (num_bytes.saturating_sub(1) as u64).min(len),
+        (None, Count::Last(num_lines)) => last_lines_start(file, len, num_lines)?,
+        (None, Count::From(num_lines)) => {
+            let reader = BufReader::with_capacity(BUF_SIZE, file);
+            return from_line(reader, num_lines, out);
+        }
+    };
+    file.seek(SeekFrom::Start(start))?;
+    io::copy(file, out)?;
+    Ok(())
+}
+
+/**
+ * 末尾の num_lines 行の先頭の位置を探す
+ *
+ * ファイルの末尾からブロック単位で読み、改行を数える。
+ * 最後の改行は最後の行の終わりなので数えない
+ */
+fn last_lines_start(file: &mut File, len: u64, num_lines: usize) -> io::Result<u64> {
+    if num_lines == 0 {
+        return Ok(len);
+    }
+    let mut buffer = vec![0; len.min(BUF_SIZE as u64) as usize];
+    let mut remaining = num_lines;
+    let mut end = len;
+    while end > 0 {
+        let start = end.saturating_sub(buffer.len() as u64);
+        let block = &mut buffer[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(block)?;
+
+        for (i, &byte) in block.iter().enumerate().rev() {
+            let pos = start + i as u64;
+            if byte == b'\n' && pos + 1 != len {
+                remaining -= 1;
+                if remaining == 0 {
+                    return Ok(pos + 1);
+                }
+            }
+        }
+        end = start;
+    }
+    Ok(0)
+}
+
+/**
+ * 標準入力などの、大きさの分からない入力の末尾を出力する
+ */
+fn tail_reader(config: &Config, mut file: impl BufRead, out: &mut dyn Write) -> io::Result<()> {
+    match (config.bytes, config.lines) {
+        (Some(Count::Last(num_bytes)), _) => last_bytes(file, num_bytes, out),
+        (Some(Count::From(num_bytes)), _) => {
+            // 読み飛ばす分は捨てる
+            let skip = num_bytes.saturating_sub(1) as u64;
+            io::copy(&mut file.by_ref().take(skip), &mut io::sink())?;
+            io::copy(&mut file, out)?;
+            Ok(())
+        }
+        (None, Count::Last(num_lines)) => last_lines(file, num_lines, out),
+        (None, Count::From(num_lines)) => from_line(file, num_lines, out),
+    }
+}
+
+/**
+ * line_num 行目から最後まで出力する (0 行目は 1 行目と同じ)
+ */
+fn from_line(mut file: impl BufRead, line_num: usize, out: &mut dyn Write) -> io::Result<()> {
+    let mut line = vec![];
+    for _ in 1..line_num {
+        line.clear();
+        if file.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+    }
+    io::copy(&mut file, out)?;
+    Ok(())
+}
+
+/**
+ * 末尾の num_bytes バイトを出力する
+ *
+ * 最後の num_bytes バイトだけをリングバッファに残すので、
+ * 入力の大きさにかかわらず使うメモリは num_bytes と読み込みバッファの分だけで済む
+ */
+fn last_bytes(mut file: impl BufRead, num_bytes: usize, out: &mut dyn Write) -> io::Result<()> {
+    let mut ring: VecDeque<u8> = VecDeque::new();
+    loop {
+        let buf = match file.fill_buf() {
+            Ok(buf) => buf,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if buf.is_empty() {
+            break;
+        }
+        let len = buf.len();
+
+        // 読み込んだ分のうち末尾に残る分だけを加え、押し出された古いバイトを捨てる
+        let keep = len.min(num_bytes);
+        let excess = (ring.len() + keep).saturating_sub(num_bytes);
+        ring.drain(..excess);
+        ring.extend(&buf[len - keep..]);
+        file.consume(len);
+    }
+
+    let (front, back) = ring.as_slices();
+    out.write_all(front)?;
+    out.write_all(back)
+}
+
+/**
+ * 末尾の num_lines 行を出力する
+ *
+ * 最後の num_lines 行だけをリングバッファに残す。押し出された行のバッファは次の行に使い回す
+ */
+fn last_lines(mut file: impl BufRead, num_lines: usize, out: &mut dyn Write) -> io::Result<()> {
+    if num_lines == 0 {
+        return Ok(());
+    }
+    let mut ring: VecDeque<Vec<u8>> = VecDeque::new();
+    let mut line = vec![];
+    loop {
+        line.clear();
+        if file.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        ring.push_back(line);
+        line = if ring.len() > num_lines {
+            ring.pop_front().unwrap_or_default()
+        } else {
+            vec![]
+        };
+    }
+
+    for line in ring {
+        out.write_all(&line)?;
+    }
+    Ok(())
+}
+
+/**
+ * 行数・バイト数の引数を変換する
+ *
+ * 先頭に '+' がある場合は、その位置から最後までとして扱う。
+ * GNU tail と同じく、先頭の '-' は無視する (-n -3 は -n 3 と同じ)
+ */
+fn parse_count(val: &str) -> MyResult<Count> {
+    let count = match val.strip_prefix('+') {
+        Some(num) => parse_size(num).map(Count::From),
+        None => parse_size(val.strip_prefix('-').unwrap_or(val)).map(Count::Last),
+    };
+    count.map_err(|e| e.describe(val))
+}
+
+#[test]
+fn test_parse_count() {
+    assert_eq!(parse_count("3").unwrap(), Count::Last(3));
+    assert_eq!(parse_count("-3").unwrap(), Count::Last(3));
+    assert_eq!(parse_count("0").unwrap(), Count::Last(0));
+    assert_eq!(parse_count("+3").unwrap(), Count::From(3));
+    assert_eq!(parse_count("+0").unwrap(), Count::From(0));
+    assert_eq!(parse_count("+2K").unwrap(), Count::From(2048));
+    assert_eq!(parse_count("1MB").unwrap(), Count::Last(1_000_000));
+
+    assert_eq!(parse_count("foo").unwrap_err().to_string(), "foo");
+    assert_eq!(parse_count("+-3").unwrap_err().to_string(), "+-3");
+    assert_eq!(parse_count("--3").unwrap_err().to_string(), "--3");
+    assert_eq!(
+        parse_count("+16E").unwrap_err().to_string(),
+        "+16E: value too large"
+    );
+}
+
+#[test]
+fn test_last_lines_start() {
+    let starts = |text: &str, num_lines| {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+        last_lines_start(&mut file, text.len() as u64, num_lines).unwrap()
+    };
+    assert_eq!(starts("one\ntwo\nthree\n", 0), 14);
+    assert_eq!(starts("one\ntwo\nthree\n", 1), 8);
+    assert_eq!(starts("one\ntwo\nthree\n", 2), 4);
+    assert_eq!(starts("one\ntwo\nthree\n", 3), 0);
+    assert_eq!(starts("one\ntwo\nthree\n", 10), 0);
+
+    // 改行で終わらない最後の行も1行と数える
+    assert_eq!(starts("one\ntwo\nthree", 1), 8);
+    assert_eq!(starts("one\n\n\n", 2), 4);
+    assert_eq!(starts("", 3), 0);
+}
+
+#[test]
+fn test_last() {
+    let text = "one\ntwo\nthree\nfour";
+    let lines = |n| {
+        let mut out = vec![];
+        last_lines(text.as_bytes(), n, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!(lines(0), "");
+    assert_eq!(lines(1), "four");
+    assert_eq!(lines(3), "two\nthree\nfour");
+    assert_eq!(lines(10), text);
+
+    // 読み込みバッファより短い単位で読んでも同じ結果になる
+    for capacity in 1..5 {
+        for n in 0..=text.len() + 1 {
+            let reader = io::BufReader::with_capacity(capacity, text.as_bytes());
+            let mut out = vec![];
+            last_bytes(reader, n, &mut out).unwrap();
+            assert_eq!(out, &text.as_bytes()[text.len().saturating_sub(n)..]);
+        }
+    }
+}
+
+#[test]
+fn test_from_line() {
+    let lines = |n| {
+        let mut out = vec![];
+        from_line(&b"one\ntwo\nthree"[..], n, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!(lines(0), "one\ntwo\nthree");
+    assert_eq!(lines(1), "one\ntwo\nthree");
+    assert_eq!(lines(3), "three");
+    assert_eq!(lines(4), "");
+}
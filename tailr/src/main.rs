@@ -0,0 +1,3 @@
+fn main() {
+    rcommon::exit_on_error("tailr", tailr::get_args().and_then(tailr::run));
+}
@@ -0,0 +1,1023 @@
+use anyhow::Result;
+use assert_cmd::Command;
+use predicates::prelude::*;
+use pretty_assertions::assert_eq;
+use rand::{distributions::Alphanumeric, Rng};
+use rcommon::testing::{self, append, wait_for};
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::process::Child;
+use std::sync::mpsc::Receiver;
+use std::thread;
+use std::time::Duration;
+use tempfile::tempdir;
+
+const PRG: &str = "tailr";
+const EMPTY: &str = "./tests/inputs/empty.txt";
+const ONE: &str = "./tests/inputs/one.txt";
+const TWO: &str = "./tests/inputs/two.txt";
+const THREE: &str = "./tests/inputs/three.txt";
+const TWELVE: &str = "./tests/inputs/twelve.txt";
+
+// --------------------------------------------------
+fn random_string() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(7)
+        .map(char::from)
+        .collect()
+}
+
+// --------------------------------------------------
+fn gen_bad_file() -> String {
+    loop {
+        let filename = random_string();
+        if fs::metadata(&filename).is_err() {
+            return filename;
+        }
+    }
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_bytes() -> Result<()> {
+    let bad = random_string();
+    Command::cargo_bin(PRG)?
+        .args(["-c", &bad, EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "illegal byte count -- {}",
+            bad
+        )));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_lines() -> Result<()> {
+    let bad = random_string();
+    Command::cargo_bin(PRG)?
+        .args(["-n", &bad, EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "illegal line count -- {}",
+            bad
+        )));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_too_large() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "+16E", EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "illegal byte count -- +16E: value too large",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bytes_and_lines() -> Result<()> {
+    let msg = "the argument '--lines <LINES>' cannot be \
+               used with '--bytes <BYTES>'";
+    Command::cargo_bin(PRG)?
+        .args(["-n", "1", "-c", "2"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(msg));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn skips_bad_file() -> Result<()> {
+    let bad = gen_bad_file();
+    let expected = format!("{bad}: .* [(]os error 2[)]");
+    Command::cargo_bin(PRG)?
+        .args([ONE, &bad, TWO])
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_match(expected)?)
+        .stdout(predicate::str::contains("==> ./tests/inputs/two.txt <=="));
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run(args: &[&str], expected_file: &str) -> Result<()> {
+    // Extra work here due to lossy UTF
+    let expected = fs::read(expected_file)?;
+    let output = Command::cargo_bin(PRG)?.args(args).output().expect("fail");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&expected)
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+// 標準入力から読む場合は、シークせずにリングバッファで末尾を残す
+fn run_stdin(args: &[&str], input_file: &str, expected_file: &str) -> Result<()> {
+    let mut file = File::open(expected_file)?;
+    let mut expected = Vec::new();
+    file.read_to_end(&mut expected)?;
+    let input = fs::read(input_file)?;
+
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .args(args)
+        .output()
+        .expect("fail");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&expected)
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn empty() -> Result<()> {
+    run(&[EMPTY], "tests/expected/empty.txt.out")
+}
+
+#[test]
+fn empty_n0() -> Result<()> {
+    run(&[EMPTY, "-n", "0"], "tests/expected/empty.txt.n0.out")
+}
+
+#[test]
+fn empty_n1() -> Result<()> {
+    run(&[EMPTY, "-n", "1"], "tests/expected/empty.txt.n1.out")
+}
+
+#[test]
+fn empty_n3() -> Result<()> {
+    run(&[EMPTY, "-n", "3"], "tests/expected/empty.txt.n3.out")
+}
+
+#[test]
+fn empty_n_plus0() -> Result<()> {
+    run(&[EMPTY, "-n", "+0"], "tests/expected/empty.txt.n+0.out")
+}
+
+#[test]
+fn empty_n_plus1() -> Result<()> {
+    run(&[EMPTY, "-n", "+1"], "tests/expected/empty.txt.n+1.out")
+}
+
+#[test]
+fn empty_n_plus3() -> Result<()> {
+    run(&[EMPTY, "-n", "+3"], "tests/expected/empty.txt.n+3.out")
+}
+
+#[test]
+fn empty_c3() -> Result<()> {
+    run(&[EMPTY, "-c", "3"], "tests/expected/empty.txt.c3.out")
+}
+
+#[test]
+fn empty_c8() -> Result<()> {
+    run(&[EMPTY, "-c", "8"], "tests/expected/empty.txt.c8.out")
+}
+
+#[test]
+fn empty_c_plus1() -> Result<()> {
+    run(&[EMPTY, "-c", "+1"], "tests/expected/empty.txt.c+1.out")
+}
+
+#[test]
+fn empty_c_plus8() -> Result<()> {
+    run(&[EMPTY, "-c", "+8"], "tests/expected/empty.txt.c+8.out")
+}
+
+#[test]
+fn empty_stdin() -> Result<()> {
+    run_stdin(&[], EMPTY, "tests/expected/empty.txt.out")
+}
+
+#[test]
+fn empty_n0_stdin() -> Result<()> {
+    run_stdin(&["-n", "0"], EMPTY, "tests/expected/empty.txt.n0.out")
+}
+
+#[test]
+fn empty_n1_stdin() -> Result<()> {
+    run_stdin(&["-n", "1"], EMPTY, "tests/expected/empty.txt.n1.out")
+}
+
+#[test]
+fn empty_n3_stdin() -> Result<()> {
+    run_stdin(&["-n", "3"], EMPTY, "tests/expected/empty.txt.n3.out")
+}
+
+#[test]
+fn empty_n_plus0_stdin() -> Result<()> {
+    run_stdin(&["-n", "+0"], EMPTY, "tests/expected/empty.txt.n+0.out")
+}
+
+#[test]
+fn empty_n_plus1_stdin() -> Result<()> {
+    run_stdin(&["-n", "+1"], EMPTY, "tests/expected/empty.txt.n+1.out")
+}
+
+#[test]
+fn empty_n_plus3_stdin() -> Result<()> {
+    run_stdin(&["-n", "+3"], EMPTY, "tests/expected/empty.txt.n+3.out")
+}
+
+#[test]
+fn empty_c3_stdin() -> Result<()> {
+    run_stdin(&["-c", "3"], EMPTY, "tests/expected/empty.txt.c3.out")
+}
+
+#[test]
+fn empty_c8_stdin() -> Result<()> {
+    run_stdin(&["-c", "8"], EMPTY, "tests/expected/empty.txt.c8.out")
+}
+
+#[test]
+fn empty_c_plus1_stdin() -> Result<()> {
+    run_stdin(&["-c", "+1"], EMPTY, "tests/expected/empty.txt.c+1.out")
+}
+
+#[test]
+fn empty_c_plus8_stdin() -> Result<()> {
+    run_stdin(&["-c", "+8"], EMPTY, "tests/expected/empty.txt.c+8.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn one() -> Result<()> {
+    run(&[ONE], "tests/expected/one.txt.out")
+}
+
+#[test]
+fn one_n0() -> Result<()> {
+    run(&[ONE, "-n", "0"], "tests/expected/one.txt.n0.out")
+}
+
+#[test]
+fn one_n1() -> Result<()> {
+    run(&[ONE, "-n", "1"], "tests/expected/one.txt.n1.out")
+}
+
+#[test]
+fn one_n3() -> Result<()> {
+    run(&[ONE, "-n", "3"], "tests/expected/one.txt.n3.out")
+}
+
+#[test]
+fn one_n_plus0() -> Result<()> {
+    run(&[ONE, "-n", "+0"], "tests/expected/one.txt.n+0.out")
+}
+
+#[test]
+fn one_n_plus1() -> Result<()> {
+    run(&[ONE, "-n", "+1"], "tests/expected/one.txt.n+1.out")
+}
+
+#[test]
+fn one_n_plus3() -> Result<()> {
+    run(&[ONE, "-n", "+3"], "tests/expected/one.txt.n+3.out")
+}
+
+#[test]
+fn one_c3() -> Result<()> {
+    run(&[ONE, "-c", "3"], "tests/expected/one.txt.c3.out")
+}
+
+#[test]
+fn one_c8() -> Result<()> {
+    run(&[ONE, "-c", "8"], "tests/expected/one.txt.c8.out")
+}
+
+#[test]
+fn one_c_plus1() -> Result<()> {
+    run(&[ONE, "-c", "+1"], "tests/expected/one.txt.c+1.out")
+}
+
+#[test]
+fn one_c_plus8() -> Result<()> {
+    run(&[ONE, "-c", "+8"], "tests/expected/one.txt.c+8.out")
+}
+
+#[test]
+fn one_stdin() -> Result<()> {
+    run_stdin(&[], ONE, "tests/expected/one.txt.out")
+}
+
+#[test]
+fn one_n0_stdin() -> Result<()> {
+    run_stdin(&["-n", "0"], ONE, "tests/expected/one.txt.n0.out")
+}
+
+#[test]
+fn one_n1_stdin() -> Result<()> {
+    run_stdin(&["-n", "1"], ONE, "tests/expected/one.txt.n1.out")
+}
+
+#[test]
+fn one_n3_stdin() -> Result<()> {
+    run_stdin(&["-n", "3"], ONE, "tests/expected/one.txt.n3.out")
+}
+
+#[test]
+fn one_n_plus0_stdin() -> Result<()> {
+    run_stdin(&["-n", "+0"], ONE, "tests/expected/one.txt.n+0.out")
+}
+
+#[test]
+fn one_n_plus1_stdin() -> Result<()> {
+    run_stdin(&["-n", "+1"], ONE, "tests/expected/one.txt.n+1.out")
+}
+
+#[test]
+fn one_n_plus3_stdin() -> Result<()> {
+    run_stdin(&["-n", "+3"], ONE, "tests/expected/one.txt.n+3.out")
+}
+
+#[test]
+fn one_c3_stdin() -> Result<()> {
+    run_stdin(&["-c", "3"], ONE, "tests/expected/one.txt.c3.out")
+}
+
+#[test]
+fn one_c8_stdin() -> Result<()> {
+    run_stdin(&["-c", "8"], ONE, "tests/expected/one.txt.c8.out")
+}
+
+#[test]
+fn one_c_plus1_stdin() -> Result<()> {
+    run_stdin(&["-c", "+1"], ONE, "tests/expected/one.txt.c+1.out")
+}
+
+#[test]
+fn one_c_plus8_stdin() -> Result<()> {
+    run_stdin(&["-c", "+8"], ONE, "tests/expected/one.txt.c+8.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn two() -> Result<()> {
+    run(&[TWO], "tests/expected/two.txt.out")
+}
+
+#[test]
+fn two_n0() -> Result<()> {
+    run(&[TWO, "-n", "0"], "tests/expected/two.txt.n0.out")
+}
+
+#[test]
+fn two_n1() -> Result<()> {
+    run(&[TWO, "-n", "1"], "tests/expected/two.txt.n1.out")
+}
+
+#[test]
+fn two_n3() -> Result<()> {
+    run(&[TWO, "-n", "3"], "tests/expected/two.txt.n3.out")
+}
+
+#[test]
+fn two_n_plus0() -> Result<()> {
+    run(&[TWO, "-n", "+0"], "tests/expected/two.txt.n+0.out")
+}
+
+#[test]
+fn two_n_plus1() -> Result<()> {
+    run(&[TWO, "-n", "+1"], "tests/expected/two.txt.n+1.out")
+}
+
+#[test]
+fn two_n_plus3() -> Result<()> {
+    run(&[TWO, "-n", "+3"], "tests/expected/two.txt.n+3.out")
+}
+
+#[test]
+fn two_c3() -> Result<()> {
+    run(&[TWO, "-c", "3"], "tests/expected/two.txt.c3.out")
+}
+
+#[test]
+fn two_c8() -> Result<()> {
+    run(&[TWO, "-c", "8"], "tests/expected/two.txt.c8.out")
+}
+
+#[test]
+fn two_c_plus1() -> Result<()> {
+    run(&[TWO, "-c", "+1"], "tests/expected/two.txt.c+1.out")
+}
+
+#[test]
+fn two_c_plus8() -> Result<()> {
+    run(&[TWO, "-c", "+8"], "tests/expected/two.txt.c+8.out")
+}
+
+#[test]
+fn two_stdin() -> Result<()> {
+    run_stdin(&[], TWO, "tests/expected/two.txt.out")
+}
+
+#[test]
+fn two_n0_stdin() -> Result<()> {
+    run_stdin(&["-n", "0"], TWO, "tests/expected/two.txt.n0.out")
+}
+
+#[test]
+fn two_n1_stdin() -> Result<()> {
+    run_stdin(&["-n", "1"], TWO, "tests/expected/two.txt.n1.out")
+}
+
+#[test]
+fn two_n3_stdin() -> Result<()> {
+    run_stdin(&["-n", "3"], TWO, "tests/expected/two.txt.n3.out")
+}
+
+#[test]
+fn two_n_plus0_stdin() -> Result<()> {
+    run_stdin(&["-n", "+0"], TWO, "tests/expected/two.txt.n+0.out")
+}
+
+#[test]
+fn two_n_plus1_stdin() -> Result<()> {
+    run_stdin(&["-n", "+1"], TWO, "tests/expected/two.txt.n+1.out")
+}
+
+#[test]
+fn two_n_plus3_stdin() -> Result<()> {
+    run_stdin(&["-n", "+3"], TWO, "tests/expected/two.txt.n+3.out")
+}
+
+#[test]
+fn two_c3_stdin() -> Result<()> {
+    run_stdin(&["-c", "3"], TWO, "tests/expected/two.txt.c3.out")
+}
+
+#[test]
+fn two_c8_stdin() -> Result<()> {
+    run_stdin(&["-c", "8"], TWO, "tests/expected/two.txt.c8.out")
+}
+
+#[test]
+fn two_c_plus1_stdin() -> Result<()> {
+    run_stdin(&["-c", "+1"], TWO, "tests/expected/two.txt.c+1.out")
+}
+
+#[test]
+fn two_c_plus8_stdin() -> Result<()> {
+    run_stdin(&["-c", "+8"], TWO, "tests/expected/two.txt.c+8.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn three() -> Result<()> {
+    run(&[THREE], "tests/expected/three.txt.out")
+}
+
+#[test]
+fn three_n0() -> Result<()> {
+    run(&[THREE, "-n", "0"], "tests/expected/three.txt.n0.out")
+}
+
+#[test]
+fn three_n1() -> Result<()> {
+    run(&[THREE, "-n", "1"], "tests/expected/three.txt.n1.out")
+}
+
+#[test]
+fn three_n3() -> Result<()> {
+    run(&[THREE, "-n", "3"], "tests/expected/three.txt.n3.out")
+}
+
+#[test]
+fn three_n_plus0() -> Result<()> {
+    run(&[THREE, "-n", "+0"], "tests/expected/three.txt.n+0.out")
+}
+
+#[test]
+fn three_n_plus1() -> Result<()> {
+    run(&[THREE, "-n", "+1"], "tests/expected/three.txt.n+1.out")
+}
+
+#[test]
+fn three_n_plus3() -> Result<()> {
+    run(&[THREE, "-n", "+3"], "tests/expected/three.txt.n+3.out")
+}
+
+#[test]
+fn three_c3() -> Result<()> {
+    run(&[THREE, "-c", "3"], "tests/expected/three.txt.c3.out")
+}
+
+#[test]
+fn three_c8() -> Result<()> {
+    run(&[THREE, "-c", "8"], "tests/expected/three.txt.c8.out")
+}
+
+#[test]
+fn three_c_plus1() -> Result<()> {
+    run(&[THREE, "-c", "+1"], "tests/expected/three.txt.c+1.out")
+}
+
+#[test]
+fn three_c_plus8() -> Result<()> {
+    run(&[THREE, "-c", "+8"], "tests/expected/three.txt.c+8.out")
+}
+
+#[test]
+fn three_stdin() -> Result<()> {
+    run_stdin(&[], THREE, "tests/expected/three.txt.out")
+}
+
+#[test]
+fn three_n0_stdin() -> Result<()> {
+    run_stdin(&["-n", "0"], THREE, "tests/expected/three.txt.n0.out")
+}
+
+#[test]
+fn three_n1_stdin() -> Result<()> {
+    run_stdin(&["-n", "1"], THREE, "tests/expected/three.txt.n1.out")
+}
+
+#[test]
+fn three_n3_stdin() -> Result<()> {
+    run_stdin(&["-n", "3"], THREE, "tests/expected/three.txt.n3.out")
+}
+
+#[test]
+fn three_n_plus0_stdin() -> Result<()> {
+    run_stdin(&["-n", "+0"], THREE, "tests/expected/three.txt.n+0.out")
+}
+
+#[test]
+fn three_n_plus1_stdin() -> Result<()> {
+    run_stdin(&["-n", "+1"], THREE, "tests/expected/three.txt.n+1.out")
+}
+
+#[test]
+fn three_n_plus3_stdin() -> Result<()> {
+    run_stdin(&["-n", "+3"], THREE, "tests/expected/three.txt.n+3.out")
+}
+
+#[test]
+fn three_c3_stdin() -> Result<()> {
+    run_stdin(&["-c", "3"], THREE, "tests/expected/three.txt.c3.out")
+}
+
+#[test]
+fn three_c8_stdin() -> Result<()> {
+    run_stdin(&["-c", "8"], THREE, "tests/expected/three.txt.c8.out")
+}
+
+#[test]
+fn three_c_plus1_stdin() -> Result<()> {
+    run_stdin(&["-c", "+1"], THREE, "tests/expected/three.txt.c+1.out")
+}
+
+#[test]
+fn three_c_plus8_stdin() -> Result<()> {
+    run_stdin(&["-c", "+8"], THREE, "tests/expected/three.txt.c+8.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn twelve() -> Result<()> {
+    run(&[TWELVE], "tests/expected/twelve.txt.out")
+}
+
+#[test]
+fn twelve_n0() -> Result<()> {
+    run(&[TWELVE, "-n", "0"], "tests/expected/twelve.txt.n0.out")
+}
+
+#[test]
+fn twelve_n1() -> Result<()> {
+    run(&[TWELVE, "-n", "1"], "tests/expected/twelve.txt.n1.out")
+}
+
+#[test]
+fn twelve_n3() -> Result<()> {
+    run(&[TWELVE, "-n", "3"], "tests/expected/twelve.txt.n3.out")
+}
+
+#[test]
+fn twelve_n_plus0() -> Result<()> {
+    run(&[TWELVE, "-n", "+0"], "tests/expected/twelve.txt.n+0.out")
+}
+
+#[test]
+fn twelve_n_plus1() -> Result<()> {
+    run(&[TWELVE, "-n", "+1"], "tests/expected/twelve.txt.n+1.out")
+}
+
+#[test]
+fn twelve_n_plus3() -> Result<()> {
+    run(&[TWELVE, "-n", "+3"], "tests/expected/twelve.txt.n+3.out")
+}
+
+#[test]
+fn twelve_c3() -> Result<()> {
+    run(&[TWELVE, "-c", "3"], "tests/expected/twelve.txt.c3.out")
+}
+
+#[test]
+fn twelve_c8() -> Result<()> {
+    run(&[TWELVE, "-c", "8"], "tests/expected/twelve.txt.c8.out")
+}
+
+#[test]
+fn twelve_c_plus1() -> Result<()> {
+    run(&[TWELVE, "-c", "+1"], "tests/expected/twelve.txt.c+1.out")
+}
+
+#[test]
+fn twelve_c_plus8() -> Result<()> {
+    run(&[TWELVE, "-c", "+8"], "tests/expected/twelve.txt.c+8.out")
+}
+
+#[test]
+fn twelve_stdin() -> Result<()> {
+    run_stdin(&[], TWELVE, "tests/expected/twelve.txt.out")
+}
+
+#[test]
+fn twelve_n0_stdin() -> Result<()> {
+    run_stdin(&["-n", "0"], TWELVE, "tests/expected/twelve.txt.n0.out")
+}
+
+#[test]
+fn twelve_n1_stdin() -> Result<()> {
+    run_stdin(&["-n", "1"], TWELVE, "tests/expected/twelve.txt.n1.out")
+}
+
+#[test]
+fn twelve_n3_stdin() -> Result<()> {
+    run_stdin(&["-n", "3"], TWELVE, "tests/expected/twelve.txt.n3.out")
+}
+
+#[test]
+fn twelve_n_plus0_stdin() -> Result<()> {
+    run_stdin(&["-n", "+0"], TWELVE, "tests/expected/twelve.txt.n+0.out")
+}
+
+#[test]
+fn twelve_n_plus1_stdin() -> Result<()> {
+    run_stdin(&["-n", "+1"], TWELVE, "tests/expected/twelve.txt.n+1.out")
+}
+
+#[test]
+fn twelve_n_plus3_stdin() -> Result<()> {
+    run_stdin(&["-n", "+3"], TWELVE, "tests/expected/twelve.txt.n+3.out")
+}
+
+#[test]
+fn twelve_c3_stdin() -> Result<()> {
+    run_stdin(&["-c", "3"], TWELVE, "tests/expected/twelve.txt.c3.out")
+}
+
+#[test]
+fn twelve_c8_stdin() -> Result<()> {
+    run_stdin(&["-c", "8"], TWELVE, "tests/expected/twelve.txt.c8.out")
+}
+
+#[test]
+fn twelve_c_plus1_stdin() -> Result<()> {
+    run_stdin(&["-c", "+1"], TWELVE, "tests/expected/twelve.txt.c+1.out")
+}
+
+#[test]
+fn twelve_c_plus8_stdin() -> Result<()> {
+    run_stdin(&["-c", "+8"], TWELVE, "tests/expected/twelve.txt.c+8.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_files() -> Result<()> {
+    run(&[EMPTY, ONE, TWO, THREE, TWELVE], "tests/expected/all.out")
+}
+
+#[test]
+fn multiple_files_n3() -> Result<()> {
+    run(
+        &[EMPTY, ONE, TWO, THREE, TWELVE, "-n", "3"],
+        "tests/expected/all.n3.out",
+    )
+}
+
+#[test]
+fn multiple_files_n_plus3() -> Result<()> {
+    run(
+        &["-n", "+3", EMPTY, ONE, TWO, THREE, TWELVE],
+        "tests/expected/all.n+3.out",
+    )
+}
+
+#[test]
+fn multiple_files_c8() -> Result<()> {
+    run(
+        &["-c", "8", EMPTY, ONE, TWO, THREE, TWELVE],
+        "tests/expected/all.c8.out",
+    )
+}
+
+#[test]
+fn multiple_files_zero() -> Result<()> {
+    // 何も出力しない場合は、ファイル名も表示しない
+    for count in ["-n0", "-c0"] {
+        Command::cargo_bin(PRG)?
+            .args([count, ONE, TWO, THREE])
+            .assert()
+            .success()
+            .stdout("");
+    }
+    Ok(())
+}
+
+#[test]
+fn multiple_files_quiet() -> Result<()> {
+    run(
+        &["-q", "-n", "1", EMPTY, ONE, TWO, THREE, TWELVE],
+        "tests/expected/all.q.out",
+    )
+}
+
+#[test]
+fn one_verbose() -> Result<()> {
+    run(&["-v", ONE], "tests/expected/one.txt.v.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn twelve_n_minus3() -> Result<()> {
+    // GNU tail と同じく、先頭の '-' は無視する
+    Command::cargo_bin(PRG)?
+        .args(["-n", "-3", TWELVE])
+        .assert()
+        .success()
+        .stdout("ten\neleven\ntwelve\n");
+    Ok(())
+}
+
+#[test]
+fn twelve_c_suffix() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "1K", TWELVE])
+        .assert()
+        .success()
+        .stdout(fs::read_to_string(TWELVE)?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn large_file() -> Result<()> {
+    // 読み込みブロックをまたいで末尾の行を探す
+    let dir = tempdir()?;
+    let path = dir.path().join("large.txt");
+    let text: String = (1..=100_000).map(|n| format!("line {n}\n")).collect();
+    fs::write(&path, &text)?;
+
+    Command::cargo_bin(PRG)?
+        .args(["-n", "20000", path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(
+            (80_001..=100_000)
+                .map(|n| format!("line {n}\n"))
+                .collect::<String>(),
+        );
+    Command::cargo_bin(PRG)?
+        .args(["-n", "20000"])
+        .write_stdin(text)
+        .assert()
+        .success()
+        .stdout(
+            (80_001..=100_000)
+                .map(|n| format!("line {n}\n"))
+                .collect::<String>(),
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(target_os = "linux")]
+fn proc_file() -> Result<()> {
+    // /proc のファイルは大きさが 0 と報告されるので、シークせずに読む
+    let version = fs::read("/proc/version")?;
+    Command::cargo_bin(PRG)?
+        .args(["-c", "5", "/proc/version"])
+        .assert()
+        .success()
+        .stdout(predicate::eq(&version[version.len() - 5..]));
+
+    let output = Command::cargo_bin(PRG)?
+        .args(["-n", "2", "/proc/self/status"])
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(stdout.lines().count(), 2);
+    assert!(stdout.contains("\nnonvoluntary_ctxt_switches:"));
+    Ok(())
+}
+
+// --------------------------------------------------
+// 追記を監視するプロセスを起動し、標準出力をスレッドで読み続ける
+fn spawn_follow(args: &[&str]) -> Result<(Child, Receiver<Vec<u8>>)> {
+    let mut cmd = std::process::Command::new(assert_cmd::cargo::cargo_bin(PRG));
+    cmd.args(args);
+    Ok(testing::spawn_follow(cmd)?)
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_appended() -> Result<()> {
+    let dir = tempdir()?;
+    let log = dir.path().join("build.log");
+    fs::write(&log, "one\ntwo\nthree\n")?;
+
+    let (mut child, rx) = spawn_follow(&["-f", "-n", "2", log.to_str().unwrap()])?;
+    let mut output = vec![];
+    wait_for(&rx, &mut output, "two\nthree\n");
+
+    append(&log, "four\n")?;
+    wait_for(&rx, &mut output, "two\nthree\nfour\n");
+
+    child.kill()?;
+    child.wait()?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_multiple_files() -> Result<()> {
+    let dir = tempdir()?;
+    let first = dir.path().join("first.log");
+    let second = dir.path().join("second.log");
+    fs::write(&first, "first\n")?;
+    fs::write(&second, "second\n")?;
+    let (first_name, second_name) = (first.to_str().unwrap(), second.to_str().unwrap());
+
+    let (mut child, rx) = spawn_follow(&["--follow", first_name, second_name])?;
+    let mut output = vec![];
+    let mut expected = format!("==> {first_name} <==\nfirst\n\n==> {second_name} <==\nsecond\n");
+    wait_for(&rx, &mut output, &expected);
+
+    // 出力するファイルが変わったときだけ、ファイル名を表示する
+    append(&second, "more\n")?;
+    expected.push_str("more\n");
+    wait_for(&rx, &mut output, &expected);
+
+    append(&first, "again\n")?;
+    expected.push_str(&format!("\n==> {first_name} <==\nagain\n"));
+    wait_for(&rx, &mut output, &expected);
+
+    child.kill()?;
+    child.wait()?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_multiple_files_n0() -> Result<()> {
+    let dir = tempdir()?;
+    let first = dir.path().join("first.log");
+    let second = dir.path().join("second.log");
+    fs::write(&first, "first\n")?;
+    fs::write(&second, "second\n")?;
+    let (first_name, second_name) = (first.to_str().unwrap(), second.to_str().unwrap());
+
+    // 最初は何も出力せず、追記されたときにファイル名を表示する
+    let (mut child, rx) = spawn_follow(&["-f", "-n", "0", first_name, second_name])?;
+    thread::sleep(Duration::from_millis(300));
+    let mut output = vec![];
+    append(&second, "more\n")?;
+    let mut expected = format!("==> {second_name} <==\nmore\n");
+    wait_for(&rx, &mut output, &expected);
+
+    append(&first, "again\n")?;
+    expected.push_str(&format!("\n==> {first_name} <==\nagain\n"));
+    wait_for(&rx, &mut output, &expected);
+
+    child.kill()?;
+    child.wait()?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_truncated() -> Result<()> {
+    let dir = tempdir()?;
+    let log = dir.path().join("build.log");
+    fs::write(&log, "a long first line\n")?;
+
+    let (mut child, rx) = spawn_follow(&["-f", log.to_str().unwrap()])?;
+    let mut output = vec![];
+    wait_for(&rx, &mut output, "a long first line\n");
+
+    fs::write(&log, "new\n")?;
+    wait_for(&rx, &mut output, "a long first line\nnew\n");
+
+    child.kill()?;
+    child.wait()?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_descriptor_keeps_renamed_file() -> Result<()> {
+    let dir = tempdir()?;
+    let log = dir.path().join("app.log");
+    let renamed = dir.path().join("app.log.1");
+    fs::write(&log, "old\n")?;
+
+    let (mut child, rx) = spawn_follow(&["-f", log.to_str().unwrap()])?;
+    let mut output = vec![];
+    wait_for(&rx, &mut output, "old\n");
+
+    // -f は開いたファイルを読み続けるので、新しく作られた同名のファイルは読まない
+    fs::rename(&log, &renamed)?;
+    fs::write(&log, "new file\n")?;
+    append(&renamed, "still old\n")?;
+    wait_for(&rx, &mut output, "old\nstill old\n");
+
+    child.kill()?;
+    child.wait()?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_name_rotated() -> Result<()> {
+    let dir = tempdir()?;
+    let log = dir.path().join("app.log");
+    fs::write(&log, "old\n")?;
+
+    let (mut child, rx) = spawn_follow(&["-F", log.to_str().unwrap()])?;
+    let mut output = vec![];
+    wait_for(&rx, &mut output, "old\n");
+
+    // ローテーション: 古いファイルを別名にして、同じ名前で新しいファイルを作る
+    fs::rename(&log, dir.path().join("app.log.1"))?;
+    fs::write(&log, "rotated\n")?;
+    wait_for(&rx, &mut output, "old\nrotated\n");
+
+    append(&log, "appended\n")?;
+    wait_for(&rx, &mut output, "old\nrotated\nappended\n");
+
+    child.kill()?;
+    child.wait()?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_name_retry() -> Result<()> {
+    let dir = tempdir()?;
+    let log = dir.path().join("late.log");
+
+    let (mut child, rx) = spawn_follow(&["-F", log.to_str().unwrap()])?;
+    thread::sleep(Duration::from_millis(300));
+    append(&log, "finally\n")?;
+
+    let mut output = vec![];
+    wait_for(&rx, &mut output, "finally\n");
+
+    child.kill()?;
+    child.wait()?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_without_retry_exits() -> Result<()> {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["--follow=name", &bad])
+        .timeout(Duration::from_secs(10))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!("{bad}: ")));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_stdin_exits() -> Result<()> {
+    // 標準入力は監視しない
+    Command::cargo_bin(PRG)?
+        .args(["-f", "-n", "1"])
+        .write_stdin("a\nb\n")
+        .timeout(Duration::from_secs(10))
+        .assert()
+        .success()
+        .stdout("b\n");
+    Ok(())
+}
@@ -0,0 +1,457 @@
+use crate::record::{content, RecordReader};
+use crate::{print_group, Config};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs::File;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+
+// ディスクに書き出すときの分割数 (フィンガープリントの PARTITION_BITS ビットずつで分ける)
+const PARTITION_BITS: u32 = 6;
+const PARTITIONS: usize = 1 << PARTITION_BITS;
+
+// 覚えている行1つあたりのメモリ使用量の見積もり (フィンガープリント、値、ハッシュ表の空き)
+const ENTRY_SIZE: usize = 64;
+
+/**
+ * 行のキーから計算した128ビットのハッシュ値
+ *
+ * 一致すれば同じ行とみなす。行そのものを覚えておかずに済む
+ */
+type Fingerprint = u128;
+
+/**
+ * これまでに現れた行
+ */
+struct Seen {
+    seq: u64,             // 最初に現れた行の番号 (0 から)
+    count: u64,           // 現れた回数
+    line: Option<String>, // まだ出力していない場合は、最初に現れた行
+}
+
+/**
+ * 離れた行も含めて重複を取り除き、最初に現れた順に出力する (--global)
+ *
+ * -c がなければ、初めて現れた行をその場で出力し、フィンガープリントだけを覚えておく。
+ * -c の場合は回数が決まるまで出力できないので、最初に現れた行も覚えておく。
+ * 覚えている分が --max-memory を超えたら、残りはディスクに書き出して処理する (spill)
+ */
+pub(crate) fn dedup(
+    config: &Config,
+    records: &mut RecordReader,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let mut seen: HashMap<Fingerprint, Seen> = HashMap::new();
+    let mut memory = 0;
+    let mut line = String::new();
+    let mut seq = 0;
+
+    loop {
+        line.clear();
+        if records.read(&mut line)? == 0 {
+            break;
+        }
+        let fingerprint = fingerprint(config, &line);
+        match seen.get_mut(&fingerprint) {
+            Some(entry) => entry.count += 1,
+            None => {
+                memory += ENTRY_SIZE;
+                let line = if config.count {
+                    memory += line.len();
+                    Some(line.clone())
+                } else {
+                    write!(out, "{}", line)?;
+                    None
+                };
+                seen.insert(
+                    fingerprint,
+                    Seen {
+                        seq,
+                        count: 1,
+                        line,
+                    },
+                );
+            }
+        }
+        seq += 1;
+
+        if let Some(max_memory) = config.max_memory.filter(|max| memory > *max) {
+            return spill(config, max_memory, seen, records, seq, out);
+        }
+    }
+
+    let mut pending: Vec<_> = seen
+        .into_values()
+        .filter(|entry| entry.line.is_some())
+        .collect();
+    pending.sort_by_key(|entry| entry.seq);
+    for entry in pending {
+        print_group(
+            config,
+            out,
+            entry.count,
+            entry.line.as_deref().unwrap_or_default(),
+        )?;
+    }
+    Ok(())
+}
+
+/**
+ * 行のキー (-f, -s, -w, -i を反映したもの) のフィンガープリントを計算する
+ *
+ * 64ビットのハッシュ値を、初期状態を変えて2つ計算してつなげる
+ */
+fn fingerprint(config: &Config, line: &str) -> Fingerprint {
+    let key = config.key.extract(content(line, config.delimiter));
+    let mut high = DefaultHasher::new();
+    key.hash(&mut high);
+    let mut low = DefaultHasher::new();
+    0xa5_u8.hash(&mut low);
+    key.hash(&mut low);
+    (u128::from(high.finish()) << 64) | u128::from(low.finish())
+}
+
+/**
+ * フィンガープリントで分けた一時ファイルに書き出してから、重複を取り除く
+ *
+ * 同じ行は必ず同じ一時ファイルに入るので、一時ファイルごとに重複を取り除き、
+ * まだ出力していない行を最初に現れた順に並べる。最後に全ての一時ファイルを行番号順にマージする。
+ * 読み込むと max_memory を超える一時ファイルは、さらに分けてから処理する
+ */
+fn spill(
+    config: &Config,
+    max_memory: usize,
+    seen: HashMap<Fingerprint, Seen>,
+    records: &mut RecordReader,
+    mut seq: u64,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let mut partitions: Vec<_> = (0..PARTITIONS).map(|_| Partition::default()).collect();
+
+    // これまでに覚えた行は、このあとに読む行より前に現れているので先に書き出す
+    for (fingerprint, entry) in &seen {
+        partitions[partition_index(fingerprint, 0)].write(fingerprint, entry)?;
+    }
+    drop(seen);
+
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if records.read(&mut line)? == 0 {
+            break;
+        }
+        let fingerprint = fingerprint(config, &line);
+        let entry = Seen {
+            seq,
+            count: 1,
+            line: Some(line),
+        };
+        partitions[partition_index(&fingerprint, 0)].write(&fingerprint, &entry)?;
+        line = entry.line.unwrap_or_default();
+        seq += 1;
+    }
+
+    let results = dedup_all(partitions, max_memory, 0)?;
+    merge(results, |entry| {
+        print_group(
+            config,
+            out,
+            entry.count,
+            entry.line.as_deref().unwrap_or_default(),
+        )
+    })
+}
+
+/**
+ * 書き出し中の一時ファイル
+ */
+#[derive(Default)]
+struct Partition {
+    file: Option<BufWriter<File>>, // 何も書いていない場合は None (一時ファイルを作らない)
+    memory: usize,                 // 全て読み込んだ場合のメモリ使用量の見積もり
+    first: Option<Fingerprint>,    // 最初に書いた行のフィンガープリント
+    distinct: bool,                // 異なるフィンガープリントの行を含むかどうか
+}
+
+impl Partition {
+    fn write(&mut self, fingerprint: &Fingerprint, entry: &Seen) -> io::Result<()> {
+        let file = match self.file.take() {
+            Some(file) => file,
+            None => BufWriter::new(tempfile::tempfile()?),
+        };
+        write_entry(self.file.insert(file), fingerprint, entry)?;
+        self.memory += ENTRY_SIZE + entry.line.as_ref().map_or(0, String::len);
+        self.distinct |= *self.first.get_or_insert(*fingerprint) != *fingerprint;
+        Ok(())
+    }
+
+    /**
+     * 読み込んでもメモリ使用量が max_memory に収まるかどうか
+     *
+     * 同じ行しか含まない場合は1行分しか覚えないので、分けても小さくならない。収まるものとする
+     */
+    fn fits(&self, max_memory: usize) -> bool {
+        !self.distinct || self.memory <= max_memory
+    }
+}
+
+/**
+ * フィンガープリントから、level 段目に分けるときの一時ファイルの番号を決める
+ *
+ * 段ごとにフィンガープリントの別のビットを使うので、異なる行はいずれ別の一時ファイルに分かれる
+ */
+fn partition_index(fingerprint: &Fingerprint, level: u32) -> usize {
+    fingerprint.rotate_right(64 + level * PARTITION_BITS) as usize % PARTITIONS
+}
+
+/**
+ * 書き出した一時ファイルを、先頭から読めるようにする
+ */
+fn rewind(writer: BufWriter<File>) -> io::Result<BufReader<File>> {
+    let mut file = writer.into_inner().map_err(|e| e.into_error())?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(BufReader::new(file))
+}
+
+/**
+ * level 段目に分けた各一時ファイルの重複を取り除く (空の一時ファイルの分は返さない)
+ */
+fn dedup_all(
+    partitions: Vec<Partition>,
+    max_memory: usize,
+    level: u32,
+) -> io::Result<Vec<BufReader<File>>> {
+    partitions
+        .into_iter()
+        .filter_map(|partition| dedup_spilled(partition, max_memory, level).transpose())
+        .collect()
+}
+
+/**
+ * 1つの一時ファイルの中で重複を取り除き、まだ出力していない行を行番号順に書いた一時ファイルを返す
+ *
+ * 読み込むと max_memory を超える場合は読み込まずに、次の段でさらに分けてからマージする
+ */
+fn dedup_spilled(
+    partition: Partition,
+    max_memory: usize,
+    level: u32,
+) -> io::Result<Option<BufReader<File>>> {
+    if partition.fits(max_memory) {
+        return partition
+            .file
+            .map(|file| rewind(file).and_then(dedup_partition))
+            .transpose();
+    }
+
+    let results = dedup_all(split(partition, level + 1)?, max_memory, level + 1)?;
+    let mut result = BufWriter::new(tempfile::tempfile()?);
+    merge(results, |entry| write_entry(&mut result, &0, &entry))?;
+    rewind(result).map(Some)
+}
+
+/**
+ * 一時ファイルの行を、level 段目の番号でさらに分ける (行番号順は保たれる)
+ */
+fn split(partition: Partition, level: u32) -> io::Result<Vec<Partition>> {
+    let mut partitions: Vec<_> = (0..PARTITIONS).map(|_| Partition::default()).collect();
+    if let Some(file) = partition.file {
+        let mut file = rewind(file)?;
+        while let Some((fingerprint, entry)) = read_entry(&mut file)? {
+            partitions[partition_index(&fingerprint, level)].write(&fingerprint, &entry)?;
+        }
+    }
+    Ok(partitions)
+}
+
+/**
+ * メモリに読み込んで重複を取り除き、まだ出力していない行を行番号順に書いた一時ファイルを返す
+ */
+fn dedup_partition(mut file: BufReader<File>) -> io::Result<BufReader<File>> {
+    let mut seen: HashMap<Fingerprint, Seen> = HashMap::new();
+    while let Some((fingerprint, entry)) = read_entry(&mut file)? {
+        // 行番号順に書き出してあるので、先に読んだものが最初に現れた行
+        match seen.get_mut(&fingerprint) {
+            Some(first) => first.count += entry.count,
+            None => {
+                seen.insert(fingerprint, entry);
+            }
+        }
+    }
+
+    let mut pending: Vec<_> = seen
+        .into_values()
+        .filter(|entry| entry.line.is_some())
+        .collect();
+    pending.sort_by_key(|entry| entry.seq);
+    let mut result = BufWriter::new(tempfile::tempfile()?);
+    for entry in pending {
+        write_entry(&mut result, &0, &entry)?;
+    }
+    rewind(result)
+}
+
+/**
+ * 行番号順に並んだ一時ファイルを、全体で行番号順になるようにマージして each に渡す
+ */
+fn merge(
+    mut results: Vec<BufReader<File>>,
+    mut each: impl FnMut(Seen) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut heads = vec![];
+    let mut heap = BinaryHeap::new();
+    for (index, result) in results.iter_mut().enumerate() {
+        let head = read_entry(result)?.map(|(_, entry)| entry);
+        if let Some(entry) = &head {
+            heap.push(Reverse((entry.seq, index)));
+        }
+        heads.push(head);
+    }
+
+    while let Some(Reverse((_, index))) = heap.pop() {
+        if let Some(entry) = heads[index].take() {
+            each(entry)?;
+        }
+        heads[index] = read_entry(&mut results[index])?.map(|(_, entry)| entry);
+        if let Some(entry) = &heads[index] {
+            heap.push(Reverse((entry.seq, index)));
+        }
+    }
+    Ok(())
+}
+
+/**
+ * 一時ファイルに1行分を書く
+ *
+ * フィンガープリント、行番号、回数、行があるかどうか、(ある場合は) 行の長さと行
+ */
+fn write_entry(out: &mut impl Write, fingerprint: &Fingerprint, entry: &Seen) -> io::Result<()> {
+    out.write_all(&fingerprint.to_le_bytes())?;
+    out.write_all(&entry.seq.to_le_bytes())?;
+    out.write_all(&entry.count.to_le_bytes())?;
+    match &entry.line {
+        Some(line) => {
+            out.write_all(&[1])?;
+            out.write_all(&(line.len() as u64).to_le_bytes())?;
+            out.write_all(line.as_bytes())
+        }
+        None => out.write_all(&[0]),
+    }
+}
+
+/**
+ * write_entry で書いた1行分を読む (最後まで読んだ場合は None)
+ */
+fn read_entry(file: &mut impl BufRead) -> io::Result<Option<(Fingerprint, Seen)>> {
+    if file.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+    let mut fingerprint = [0; 16];
+    file.read_exact(&mut fingerprint)?;
+    let seq = read_u64(file)?;
+    let count = read_u64(file)?;
+    let mut has_line = [0];
+    file.read_exact(&mut has_line)?;
+    let line = if has_line[0] == 1 {
+        let mut line = vec![0; read_u64(file)? as usize];
+        file.read_exact(&mut line)?;
+        Some(String::from_utf8(line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?)
+    } else {
+        None
+    };
+    Ok(Some((
+        Fingerprint::from_le_bytes(fingerprint),
+        Seen { seq, count, line },
+    )))
+}
+
+fn read_u64(file: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    file.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dedup_spilled, fingerprint, merge, split, Partition, Seen};
+    use crate::{run_with, Config};
+    use std::io::{self, Cursor};
+
+    fn dedup(config: Config, input: &str) -> String {
+        let mut out = vec![];
+        let config = config.global(true);
+        assert!(run_with(config, &mut Cursor::new(input), &mut out, &mut io::sink()).is_ok());
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_in_memory() {
+        let input = "b\na\nb\nc\na\nb";
+        assert_eq!(dedup(Config::new("-"), input), "b\na\nc\n");
+        assert_eq!(
+            dedup(Config::new("-").count(true), input),
+            "   3 b\n   2 a\n   1 c\n"
+        );
+        assert_eq!(
+            dedup(Config::new("-").ignore_case(true), "x\nY\nX\ny\n"),
+            "x\nY\n"
+        );
+    }
+
+    #[test]
+    fn test_spill() {
+        // 覚えている行が上限を超えた時点から一時ファイルに書き出す
+        let input: String = (0..1000).map(|n| format!("{}\n", n * 7 % 300)).collect();
+        for count in [false, true] {
+            let expected = dedup(Config::new("-").count(count), &input);
+            for max_memory in [0, 1000, 10_000] {
+                let config = Config::new("-").count(count).max_memory(max_memory);
+                assert_eq!(dedup(config, &input), expected);
+            }
+        }
+    }
+
+    /**
+     * 100種類の行が2回ずつ現れる一時ファイル
+     */
+    fn large_partition() -> Partition {
+        let config = Config::new("-").count(true);
+        let mut partition = Partition::default();
+        for seq in 0..200 {
+            let line = format!("{}\n", seq % 100);
+            let fingerprint = fingerprint(&config, &line);
+            let entry = Seen {
+                seq,
+                count: 1,
+                line: Some(line),
+            };
+            partition.write(&fingerprint, &entry).unwrap();
+        }
+        partition
+    }
+
+    #[test]
+    fn test_split_large_partition() {
+        let max_memory = 1000;
+        let partition = large_partition();
+        let memory = partition.memory;
+        assert!(!partition.fits(max_memory));
+
+        // 上限を超える一時ファイルは読み込まずに、次の段でさらに小さく分ける
+        let partitions = split(partition, 1).unwrap();
+        assert_eq!(partitions.iter().map(|p| p.memory).sum::<usize>(), memory);
+        assert!(partitions.iter().all(|p| p.memory < memory));
+        assert!(partitions.iter().filter(|p| p.file.is_some()).count() > 1);
+
+        // 分けてから処理しても、最初に現れた順に回数を数える
+        let result = dedup_spilled(large_partition(), max_memory, 0).unwrap();
+        let mut groups = vec![];
+        merge(result.into_iter().collect(), |entry| {
+            groups.push((entry.count, entry.line.unwrap_or_default()));
+            Ok(())
+        })
+        .unwrap();
+        let expected: Vec<_> = (0..100).map(|n| (2, format!("{}\n", n))).collect();
+        assert_eq!(groups, expected);
+    }
+}
@@ -0,0 +1,116 @@
+use std::borrow::Cow;
+
+/**
+ * 隣り合う行が同じかどうかを判定するときに比べる部分 (-f, -s, -w, -i)
+ *
+ * 何も指定しない場合は、行全体 (行末の空白と区切りは除く) を比べる
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Key {
+    pub skip_fields: usize,         // 先頭から読み飛ばすフィールドの数
+    pub skip_chars: usize,          // フィールドのあとに読み飛ばす文字数
+    pub check_chars: Option<usize>, // 比べる最大の文字数
+    pub ignore_case: bool,
+}
+
+impl Key {
+    /**
+     * 行から比べる部分を取り出す
+     *
+     * フィールドは、空白 (スペースとタブ) の並びとそれに続く空白以外の並び。
+     * 大文字と小文字を区別しない場合は、小文字にそろえたものを返す
+     */
+    pub fn extract<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        let mut rest = line;
+        for _ in 0..self.skip_fields {
+            rest = rest.trim_start_matches(is_blank);
+            rest = rest.trim_start_matches(|c| !is_blank(c));
+        }
+        rest = match rest.char_indices().nth(self.skip_chars) {
+            Some((pos, _)) => &rest[pos..],
+            None => "",
+        };
+        if let Some((pos, _)) = self
+            .check_chars
+            .and_then(|check_chars| rest.char_indices().nth(check_chars))
+        {
+            rest = &rest[..pos];
+        }
+
+        if self.ignore_case {
+            Cow::Owned(rest.to_lowercase())
+        } else {
+            Cow::Borrowed(rest)
+        }
+    }
+
+    /**
+     * 2つの行を同じとみなすかどうか
+     */
+    pub fn same(&self, a: &str, b: &str) -> bool {
+        self.extract(a) == self.extract(b)
+    }
+}
+
+fn is_blank(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Key;
+
+    #[test]
+    fn test_whole_line() {
+        let key = Key::default();
+        assert_eq!(key.extract("a b"), "a b");
+        assert!(key.same("a", "a"));
+        assert!(!key.same("a", "a "));
+        assert!(!key.same("a", "A"));
+    }
+
+    #[test]
+    fn test_skip_fields() {
+        let key = Key {
+            skip_fields: 2,
+            ..Key::default()
+        };
+        assert_eq!(key.extract("one two three"), " three");
+        assert_eq!(key.extract("  one\t two  three"), "  three");
+        assert_eq!(key.extract("one two"), "");
+        assert!(key.same("1 a x", "2 b x"));
+        assert!(!key.same("1 a x", "2 b  x"));
+    }
+
+    #[test]
+    fn test_skip_and_check_chars() {
+        let key = Key {
+            skip_fields: 1,
+            skip_chars: 1,
+            check_chars: Some(3),
+            ..Key::default()
+        };
+        assert_eq!(key.extract("id abcdef"), "abc");
+        assert_eq!(key.extract("id ab"), "ab");
+        assert_eq!(key.extract("id"), "");
+
+        // 文字単位で数える
+        let key = Key {
+            skip_chars: 1,
+            check_chars: Some(2),
+            ..Key::default()
+        };
+        assert_eq!(key.extract("日本語です"), "本語");
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        let key = Key {
+            ignore_case: true,
+            ..Key::default()
+        };
+        assert!(key.same("Apple", "aPPLE"));
+        assert!(key.same("Ärger", "ärger"));
+        assert!(!key.same("apple", "apples"));
+    }
+}
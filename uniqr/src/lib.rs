@@ -1,11 +1,39 @@
-use clap::{App, Arg};
+use clap::{Parser, ValueEnum};
+use key::Key;
+use rcommon::cli::GenerateArgs;
+use rcommon::size::{parse_size, SizeError};
+use rcommon::{open, MyResult, Reporter};
+use record::{content, RecordReader};
 use std::{
-    error::Error,
+    ffi::OsString,
     fs::File,
-    io::{self, BufRead, BufReader, Write},
+    io::{self, BufRead, Write},
 };
 
-type MyResult<T> = Result<T, Box<dyn Error>>;
+mod global;
+mod key;
+mod record;
+
+/**
+ * -D で重複した行を全て出力するときの、グループの区切り方
+ */
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Delimit {
+    None,     // 区切らない
+    Prepend,  // 各グループの前に空行を入れる
+    Separate, // グループの間に空行を入れる
+}
+
+/**
+ * --group で全ての行を出力するときの、空行を入れる位置
+ */
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Group {
+    Separate, // グループの間
+    Prepend,  // 各グループの前
+    Append,   // 各グループの後
+    Both,     // 各グループの前と後
+}
 
 #[derive(Debug)]
 pub struct Config {
@@ -15,93 +43,453 @@ pub struct Config {
     out_file: Option<String>,
     // 各行の出現回数を表示するかどうかのフラグ
     count: bool,
+    // 重複した行だけを出力する (-d)
+    repeated: bool,
+    // 重複しなかった行だけを出力する (-u)
+    unique: bool,
+    // 重複した行を1行にまとめずに全て出力する (-D)
+    all_repeated: Option<Delimit>,
+    // 全ての行を出力し、グループを空行で区切る (--group)
+    group: Option<Group>,
+    // 隣り合う行を比べるときに使う部分
+    key: Key,
+    // 行の区切り (-z の場合は NUL)
+    delimiter: u8,
+    // 隣り合っていない行も含めて重複を取り除く (--global)
+    global: bool,
+    // --global で覚えておく行に使うメモリの上限 (バイト数)
+    max_memory: Option<usize>,
+}
+
+impl Config {
+    /**
+     * 入力ファイル名 ("-" は標準入力) から、標準出力へ書き出す設定を作る
+     */
+    pub fn new(in_file: &str) -> Config {
+        Config {
+            in_file: in_file.to_string(),
+            out_file: None,
+            count: false,
+            repeated: false,
+            unique: false,
+            all_repeated: None,
+            group: None,
+            key: Key::default(),
+            delimiter: b'\n',
+            global: false,
+            max_memory: None,
+        }
+    }
+
+    /**
+     * 出力先のファイル
+     */
+    pub fn out_file(mut self, out_file: &str) -> Config {
+        self.out_file = Some(out_file.to_string());
+        self
+    }
+
+    /**
+     * 各行の出現回数を表示する
+     */
+    pub fn count(mut self, count: bool) -> Config {
+        self.count = count;
+        self
+    }
+
+    /**
+     * 2行以上続いた行だけを出力する
+     */
+    pub fn repeated(mut self, repeated: bool) -> Config {
+        self.repeated = repeated;
+        self
+    }
+
+    /**
+     * 1行だけの (隣の行と重複しなかった) 行だけを出力する
+     */
+    pub fn unique(mut self, unique: bool) -> Config {
+        self.unique = unique;
+        self
+    }
+
+    /**
+     * 2行以上続いた行を、まとめずに全て出力する
+     */
+    pub fn all_repeated(mut self, delimit: Delimit) -> Config {
+        self.all_repeated = Some(delimit);
+        self
+    }
+
+    /**
+     * 全ての行を出力し、グループの区切りに空行を入れる
+     */
+    pub fn group(mut self, group: Group) -> Config {
+        self.group = Some(group);
+        self
+    }
+
+    /**
+     * 行の区切りにするバイト (-z の場合は b'\0')
+     *
+     * グループを区切る空行も、このバイトだけの行になる
+     */
+    pub fn delimiter(mut self, delimiter: u8) -> Config {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /**
+     * 隣り合っていない行も含めて重複を取り除き、最初に現れた順に出力する
+     */
+    pub fn global(mut self, global: bool) -> Config {
+        self.global = global;
+        self
+    }
+
+    /**
+     * --global で覚えておく行がこのバイト数を超えたら、一時ファイルを使う
+     */
+    pub fn max_memory(mut self, max_memory: usize) -> Config {
+        self.max_memory = Some(max_memory);
+        self
+    }
+
+    /**
+     * 大文字と小文字を区別せずに比べる
+     */
+    pub fn ignore_case(mut self, ignore_case: bool) -> Config {
+        self.key.ignore_case = ignore_case;
+        self
+    }
+
+    /**
+     * 先頭の skip_fields 個のフィールドを比べない
+     */
+    pub fn skip_fields(mut self, skip_fields: usize) -> Config {
+        self.key.skip_fields = skip_fields;
+        self
+    }
+
+    /**
+     * (フィールドを読み飛ばしたあとの) 先頭の skip_chars 文字を比べない
+     */
+    pub fn skip_chars(mut self, skip_chars: usize) -> Config {
+        self.key.skip_chars = skip_chars;
+        self
+    }
+
+    /**
+     * 最大で check_chars 文字だけを比べる
+     */
+    pub fn check_chars(mut self, check_chars: usize) -> Config {
+        self.key.check_chars = Some(check_chars);
+        self
+    }
+}
+
+// コマンドライン引数の定義 (ヘルプ・補完スクリプト・manページにも使われる)
+#[derive(Debug, Parser)]
+#[command(
+    name = "uniqr",
+    version = "0.1.0",
+    author = "hoge",
+    about = "Rust uniq"
+)]
+struct Args {
+    /// Input file
+    #[arg(value_name = "IN_FILE", default_value = "-")]
+    in_file: String,
+
+    /// Output file
+    #[arg(value_name = "OUT_FILE")]
+    out_file: Option<String>,
+
+    /// Show counts
+    #[arg(short = 'c', long = "count")]
+    count: bool,
+
+    /// Only print duplicate lines, one for each group
+    #[arg(short = 'd', long = "repeated")]
+    repeated: bool,
+
+    /// Print all duplicate lines, optionally delimiting groups with blank lines
+    #[arg(
+        short = 'D',
+        long = "all-repeated",
+        value_name = "METHOD",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "none",
+        conflicts_with_all = ["count", "unique"]
+    )]
+    all_repeated: Option<Delimit>,
+
+    /// Print all lines, delimiting groups with blank lines
+    #[arg(
+        long = "group",
+        value_name = "METHOD",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "separate",
+        conflicts_with_all = ["count", "repeated", "all_repeated", "unique"]
+    )]
+    group: Option<Group>,
+
+    /// Only print unique lines
+    #[arg(short = 'u', long = "unique")]
+    unique: bool,
+
+    /// Ignore differences in case when comparing
+    #[arg(short = 'i', long = "ignore-case")]
+    ignore_case: bool,
+
+    /// Avoid comparing the first N fields
+    #[arg(
+        short = 'f',
+        long = "skip-fields",
+        value_name = "N",
+        default_value = "0"
+    )]
+    skip_fields: usize,
+
+    /// Avoid comparing the first N characters
+    #[arg(
+        short = 's',
+        long = "skip-chars",
+        value_name = "N",
+        default_value = "0"
+    )]
+    skip_chars: usize,
+
+    /// Compare no more than N characters in lines
+    #[arg(short = 'w', long = "check-chars", value_name = "N")]
+    check_chars: Option<usize>,
+
+    /// Line delimiter is NUL, not newline
+    #[arg(short = 'z', long = "zero-terminated")]
+    zero_terminated: bool,
+
+    /// Remove all duplicate lines, not only adjacent ones, keeping the first occurrences in order
+    #[arg(
+        long = "global",
+        conflicts_with_all = ["repeated", "all_repeated", "unique", "group"]
+    )]
+    global: bool,
+
+    /// Memory limit for --global before using temporary files (suffixes such as K, MB or GiB
+    /// are allowed)
+    #[arg(
+        long = "max-memory",
+        value_name = "SIZE",
+        requires = "global",
+        value_parser = parse_memory
+    )]
+    max_memory: Option<usize>,
+
+    #[command(flatten)]
+    generate: GenerateArgs,
 }
 
 pub fn get_args() -> MyResult<Config> {
-    let matches = App::new("uniqr")
-        .version("0.1.0")
-        .author("hoge")
-        .about("Rust uniq")
-        .arg(
-            Arg::with_name("in_file")
-                .value_name("IN_FILE")
-                .help("Input file")
-                .default_value("-")
-                .required(false)
-        )
-        .arg(
-            Arg::with_name("out_file")
-                .value_name("OUT_FILE")
-                .help("Output file")
-                .required(false)
-        )
-        .arg(
-            Arg::with_name("count")
-                .short("c")
-                .long("count")
-                .help("Show counts")
-                .takes_value(false)
-        )
-        .get_matches();
+    get_args_from(std::env::args_os())
+}
+
+/**
+ * 指定した引数列を解析し、Config構造体を返す
+ *
+ * 先頭の要素はプログラム名として扱われる
+ */
+pub fn get_args_from<I, T>(args: I) -> MyResult<Config>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let args = Args::parse_from(args);
+    args.generate.exit_if_requested::<Args>();
 
     Ok(Config {
-        in_file: matches.value_of_lossy("in_file").unwrap().to_string(),
-        out_file: matches.value_of("out_file").map(|s| s.to_string()),
-        count: matches.is_present("count"),
+        in_file: args.in_file,
+        out_file: args.out_file,
+        count: args.count,
+        repeated: args.repeated,
+        unique: args.unique,
+        all_repeated: args.all_repeated,
+        group: args.group,
+        key: Key {
+            skip_fields: args.skip_fields,
+            skip_chars: args.skip_chars,
+            check_chars: args.check_chars,
+            ignore_case: args.ignore_case,
+        },
+        delimiter: if args.zero_terminated { b'\0' } else { b'\n' },
+        global: args.global,
+        max_memory: args.max_memory,
     })
 }
 
 pub fn run(config: Config) -> MyResult<()> {
-    let mut file = open(&config.in_file)
-        .map_err(|e| format!("{}: {}", config.in_file, e))?;
-    let mut out_file: Box<dyn Write> = match &config.out_file {
-        Some(out_name) => Box::new(File::create(out_name)?),
-        _ => Box::new(io::stdout()),
-    };
-
+    run_with(
+        config,
+        &mut io::stdin().lock(),
+        &mut io::stdout().lock(),
+        &mut io::stderr(),
+    )
+}
 
-    let mut print = |count: u64, text: &str| -> MyResult<()> {
-        if count > 0 {
-            if config.count {
-                write!(out_file, "{:>4} {}", count, text)?;
-            } else {
-                write!(out_file, "{}", text)?;
-            }
+/**
+ * 標準入力・標準出力・標準エラーの代わりに、指定したリーダーとライターを使って実行する
+ *
+ * 出力ファイルが指定されている場合は out ではなくそのファイルに書き出す
+ */
+pub fn run_with(
+    config: Config,
+    stdin: &mut dyn BufRead,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> MyResult<()> {
+    let mut reporter = Reporter::new("uniqr", err);
+    let file = match open(&config.in_file, stdin) {
+        Ok(file) => file,
+        Err(err) => {
+            reporter.error(&config.in_file, err);
+            return reporter.finish();
         }
-        Ok(())
+    };
+    let mut out_file: Box<dyn Write + '_> = match &config.out_file {
+        Some(out_name) => match File::create(out_name) {
+            Ok(file) => Box::new(file),
+            Err(err) => {
+                reporter.error(out_name, err);
+                return reporter.finish();
+            }
+        },
+        _ => Box::new(out),
     };
 
+    let mut records = RecordReader::new(file, config.delimiter);
+    if config.global {
+        global::dedup(&config, &mut records, &mut out_file)?;
+        return Ok(());
+    }
+
+    let separator = [config.delimiter];
     let mut line = String::new();
-    let mut previous = String::new();
+    let mut previous = String::new(); // 今のグループの最初の行
     let mut count: u64 = 0;
+    let mut groups: u64 = 0; // -D で出力したグループの数
 
     loop {
-        let bytes = file.read_line(&mut line)?;
+        line.clear();
+        let bytes = records.read(&mut line)?;
         if bytes == 0 {
             break;
         }
 
-        if line.trim_end() != previous.trim_end() {
-            print(count, &previous);
-            previous = line.clone();
-            count = 0;
+        let same = count > 0
+            && config.key.same(
+                content(&line, config.delimiter),
+                content(&previous, config.delimiter),
+            );
+        if same {
+            count += 1;
+            // -D の場合は、2行目が見つかった時点でグループの最初の行から出力していく
+            if let (Some(delimit), false) = (config.all_repeated, config.unique) {
+                if count == 2 {
+                    if delimit == Delimit::Prepend || (delimit == Delimit::Separate && groups > 0) {
+                        out_file.write_all(&separator)?;
+                    }
+                    write!(out_file, "{}", previous)?;
+                    groups += 1;
+                }
+                write!(out_file, "{}", line)?;
+            }
+            if config.group.is_some() {
+                write!(out_file, "{}", line)?;
+            }
+            continue;
         }
 
-        count += 1;
-        line.clear();
+        match config.group {
+            Some(group) => {
+                // グループの間は、どの場合も空行を1つだけ入れる (count が 0 の場合は最初のグループ)
+                if count > 0 || matches!(group, Group::Prepend | Group::Both) {
+                    out_file.write_all(&separator)?;
+                }
+                write!(out_file, "{}", line)?;
+            }
+            None if config.all_repeated.is_none() => {
+                print_group(&config, &mut out_file, count, &previous)?;
+            }
+            None => {}
+        }
+        std::mem::swap(&mut previous, &mut line);
+        count = 1;
     }
 
-    print(count, &previous)?;
+    match config.group {
+        Some(Group::Append | Group::Both) if count > 0 => out_file.write_all(&separator)?,
+        Some(_) => {}
+        None if config.all_repeated.is_none() => {
+            print_group(&config, &mut out_file, count, &previous)?
+        }
+        None => {}
+    }
     Ok(())
+}
 
+/**
+ * 1つのグループ (同じとみなした隣り合う行) を、-d や -u に合わせて1行にまとめて出力する
+ */
+fn print_group(config: &Config, out: &mut dyn Write, count: u64, text: &str) -> io::Result<()> {
+    let selected = match (config.repeated, config.unique) {
+        (true, true) => false,
+        (true, false) => count > 1,
+        (false, true) => count == 1,
+        (false, false) => true,
+    };
+    if count > 0 && selected {
+        if config.count {
+            write!(out, "{:>4} {}", count, text)?;
+        } else {
+            write!(out, "{}", text)?;
+        }
+    }
+    Ok(())
 }
 
 /**
- * ファイルオープン
+ * --max-memory の値を変換する
+ *
+ * headr や tailr の -c・-n と同じく、10K や 4MiB のような単位が使える
  */
-fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(std::fs::File::open(filename)?))),
+fn parse_memory(val: &str) -> Result<usize, String> {
+    parse_size(val).map_err(|e| match e {
+        SizeError::Invalid => format!("invalid size '{}'", val),
+        SizeError::TooLarge => e.describe(val).to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_memory;
+
+    #[test]
+    fn test_parse_memory() {
+        assert_eq!(parse_memory("100"), Ok(100));
+        assert_eq!(parse_memory("4K"), Ok(4096));
+        assert_eq!(parse_memory("2m"), Ok(2 << 20));
+        assert_eq!(parse_memory("1G"), Ok(1 << 30));
+        assert_eq!(parse_memory("1GiB"), Ok(1 << 30));
+        assert_eq!(parse_memory("4KB"), Ok(4000));
+        assert_eq!(parse_memory("2b"), Ok(1024));
+        assert!(parse_memory("").is_err());
+        assert!(parse_memory("K").is_err());
+        assert!(parse_memory("10X").is_err());
+        assert!(parse_memory("-1").is_err());
+        assert_eq!(
+            parse_memory("16E"),
+            Err("16E: value too large".to_string())
+        );
     }
-}
\ No newline at end of file
+}
@@ -1,6 +1,3 @@
 fn main() {
-    if let Err(e) = uniqr::get_args().and_then(uniqr::run) {
-        eprintln!("{}", e);
-        std::process::exit(1);
-    }
+    rcommon::exit_on_error("uniqr", uniqr::get_args().and_then(uniqr::run));
 }
@@ -0,0 +1,90 @@
+use std::io::{self, BufRead};
+
+/**
+ * delimiter で終わるレコード (通常は行、-z の場合は NUL で終わる並び) を1つずつ読む
+ */
+pub(crate) struct RecordReader<'a> {
+    reader: Box<dyn BufRead + 'a>,
+    delimiter: u8,
+    buf: Vec<u8>,
+}
+
+impl<'a> RecordReader<'a> {
+    pub fn new(reader: Box<dyn BufRead + 'a>, delimiter: u8) -> RecordReader<'a> {
+        RecordReader {
+            reader,
+            delimiter,
+            buf: vec![],
+        }
+    }
+
+    /**
+     * 区切りまで (区切りを含む) を record の末尾に加え、読んだバイト数を返す
+     *
+     * BufRead::read_line と同じく、EOF の場合は 0 を返し、UTF-8 でない場合はエラーにする
+     */
+    pub fn read(&mut self, record: &mut String) -> io::Result<usize> {
+        self.buf.clear();
+        let bytes = self.reader.read_until(self.delimiter, &mut self.buf)?;
+        let text = std::str::from_utf8(&self.buf).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "stream did not contain valid UTF-8",
+            )
+        })?;
+        record.push_str(text);
+        Ok(bytes)
+    }
+}
+
+/**
+ * 比べるときに使うレコードの中身
+ *
+ * 末尾の区切りと、その手前の空白 ("\r" を含む) は除く。
+ * "a \n" や "a\r\n" は "a\n" と同じ行として扱う
+ */
+pub(crate) fn content(record: &str, delimiter: u8) -> &str {
+    record
+        .strip_suffix(char::from(delimiter))
+        .unwrap_or(record)
+        .trim_end()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{content, RecordReader};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read() {
+        let input = Cursor::new("a\nb\0c\nd\0e");
+        let mut records = RecordReader::new(Box::new(input), b'\0');
+        let mut record = String::new();
+        let mut all = vec![];
+        while records.read(&mut record).unwrap() > 0 {
+            all.push(record.clone());
+            record.clear();
+        }
+        assert_eq!(all, ["a\nb\0", "c\nd\0", "e"]);
+        assert_eq!(content("c\nd\0", b'\0'), "c\nd");
+        assert_eq!(content("e", b'\0'), "e");
+    }
+
+    #[test]
+    fn test_content_trailing_space() {
+        assert_eq!(content("a\n", b'\n'), "a");
+        assert_eq!(content("a \n", b'\n'), "a");
+        assert_eq!(content("a\r\n", b'\n'), "a");
+        assert_eq!(content(" a\t", b'\n'), " a");
+        assert_eq!(content("a \0", b'\0'), "a");
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        let input = Cursor::new(&b"ok\n\xff\n"[..]);
+        let mut records = RecordReader::new(Box::new(input), b'\n');
+        let mut record = String::new();
+        assert_eq!(records.read(&mut record).unwrap(), 3);
+        assert!(records.read(&mut record).is_err());
+    }
+}
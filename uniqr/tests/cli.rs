@@ -617,3 +617,324 @@ fn t6_outfile_count() -> Result<()> {
 fn t6_stdin_outfile_count() -> Result<()> {
     run_stdin_outfile_count(&T6)
 }
+
+// --------------------------------------------------
+const FIELDS: &str = "tests/inputs/fields.txt";
+
+// --------------------------------------------------
+fn run_args(args: &[&str], input: &str, expected_file: &str) -> Result<()> {
+    let expected = fs::read_to_string(expected_file)?;
+    let output = Command::cargo_bin(PRG)?
+        .args(args)
+        .arg(input)
+        .output()
+        .expect("fail");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    assert_eq!(stdout, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn three_repeated() -> Result<()> {
+    run_args(&["-d"], THREE.input, "tests/expected/three.txt.d.out")
+}
+
+#[test]
+fn three_unique() -> Result<()> {
+    run_args(&["--unique"], THREE.input, "tests/expected/three.txt.u.out")
+}
+
+#[test]
+fn three_repeated_and_unique() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-d", "-u", THREE.input])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+#[test]
+fn three_all_repeated() -> Result<()> {
+    run_args(&["-D"], THREE.input, "tests/expected/three.txt.D.out")
+}
+
+#[test]
+fn three_all_repeated_separate() -> Result<()> {
+    run_args(
+        &["--all-repeated=separate"],
+        THREE.input,
+        "tests/expected/three.txt.D.separate.out",
+    )
+}
+
+#[test]
+fn three_all_repeated_prepend() -> Result<()> {
+    run_args(
+        &["-D=prepend"],
+        THREE.input,
+        "tests/expected/three.txt.D.prepend.out",
+    )
+}
+
+#[test]
+fn dies_all_repeated_and_count() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-D", "-c", THREE.input])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Command::cargo_bin(PRG)?
+        .args(["--all-repeated=bogus", THREE.input])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value 'bogus'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn fields_skip_fields() -> Result<()> {
+    run_args(&["-f", "1"], FIELDS, "tests/expected/fields.txt.f1.out")
+}
+
+#[test]
+fn fields_check_chars_repeated() -> Result<()> {
+    run_args(
+        &["-f", "1", "-w", "7", "-d"],
+        FIELDS,
+        "tests/expected/fields.txt.f1.w7.d.out",
+    )
+}
+
+#[test]
+fn fields_check_chars_unique() -> Result<()> {
+    run_args(
+        &["--skip-fields=1", "--check-chars=7", "-u"],
+        FIELDS,
+        "tests/expected/fields.txt.f1.w7.u.out",
+    )
+}
+
+#[test]
+fn fields_check_chars_all_repeated() -> Result<()> {
+    run_args(
+        &["-f", "1", "-w", "7", "--all-repeated=separate"],
+        FIELDS,
+        "tests/expected/fields.txt.f1.w7.D.separate.out",
+    )
+}
+
+#[test]
+fn fields_skip_chars_ignore_case() -> Result<()> {
+    run_args(
+        &["-s", "2", "-w", "5", "-i"],
+        FIELDS,
+        "tests/expected/fields.txt.s2.w5.i.out",
+    )
+}
+
+#[test]
+fn fields_skip_chars_ignore_case_count() -> Result<()> {
+    run_args(
+        &[
+            "--skip-chars",
+            "2",
+            "--check-chars",
+            "5",
+            "--ignore-case",
+            "-c",
+        ],
+        FIELDS,
+        "tests/expected/fields.txt.s2.w5.i.c.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn three_group_separate() -> Result<()> {
+    run_args(
+        &["--group"],
+        THREE.input,
+        "tests/expected/three.txt.group.separate.out",
+    )?;
+    run_args(
+        &["--group=separate"],
+        THREE.input,
+        "tests/expected/three.txt.group.separate.out",
+    )
+}
+
+#[test]
+fn three_group_prepend() -> Result<()> {
+    run_args(
+        &["--group=prepend"],
+        THREE.input,
+        "tests/expected/three.txt.group.prepend.out",
+    )
+}
+
+#[test]
+fn three_group_append() -> Result<()> {
+    run_args(
+        &["--group=append"],
+        THREE.input,
+        "tests/expected/three.txt.group.append.out",
+    )
+}
+
+#[test]
+fn three_group_both() -> Result<()> {
+    run_args(
+        &["--group=both"],
+        THREE.input,
+        "tests/expected/three.txt.group.both.out",
+    )
+}
+
+#[test]
+fn empty_group_both() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--group=both", EMPTY.input])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+#[test]
+fn dies_group_and_count() -> Result<()> {
+    for opt in ["-c", "-d", "-D", "-u"] {
+        Command::cargo_bin(PRG)?
+            .args(["--group", opt, THREE.input])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+const ZERO: &str = "tests/inputs/zero.txt";
+
+#[test]
+fn zero_terminated() -> Result<()> {
+    run_args(&["-z"], ZERO, "tests/expected/zero.txt.z.out")
+}
+
+#[test]
+fn zero_terminated_count() -> Result<()> {
+    run_args(
+        &["--zero-terminated", "-c"],
+        ZERO,
+        "tests/expected/zero.txt.z.c.out",
+    )
+}
+
+#[test]
+fn zero_terminated_group_both() -> Result<()> {
+    run_args(
+        &["-z", "-i", "--group=both"],
+        ZERO,
+        "tests/expected/zero.txt.z.i.group.both.out",
+    )
+}
+
+#[test]
+fn zero_terminated_all_repeated() -> Result<()> {
+    run_args(
+        &["-z", "--all-repeated=separate"],
+        ZERO,
+        "tests/expected/zero.txt.z.D.separate.out",
+    )
+}
+
+// --------------------------------------------------
+const GLOBAL: &str = "tests/inputs/global.txt";
+
+#[test]
+fn global() -> Result<()> {
+    run_args(
+        &["--global"],
+        GLOBAL,
+        "tests/expected/global.txt.global.out",
+    )
+}
+
+#[test]
+fn global_count() -> Result<()> {
+    run_args(
+        &["--global", "-c"],
+        GLOBAL,
+        "tests/expected/global.txt.global.c.out",
+    )
+}
+
+#[test]
+fn global_ignore_case_count() -> Result<()> {
+    run_args(
+        &["--global", "-i", "-c"],
+        GLOBAL,
+        "tests/expected/global.txt.global.i.c.out",
+    )
+}
+
+#[test]
+fn global_max_memory() -> Result<()> {
+    // 上限を超えたら一時ファイルを使うが、結果は変わらない
+    for max_memory in ["0", "100", "1K", "2KB", "1MiB"] {
+        run_args(
+            &["--global", "--max-memory", max_memory],
+            GLOBAL,
+            "tests/expected/global.txt.global.out",
+        )?;
+        run_args(
+            &["--global", "-c", "--max-memory", max_memory],
+            GLOBAL,
+            "tests/expected/global.txt.global.c.out",
+        )?;
+    }
+    Ok(())
+}
+
+#[test]
+fn dies_global_options() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--global", "-d", GLOBAL])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Command::cargo_bin(PRG)?
+        .args(["--max-memory", "1K", GLOBAL])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--global"));
+    Command::cargo_bin(PRG)?
+        .args(["--global", "--max-memory", "1X", GLOBAL])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid size '1X'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn trailing_whitespace() -> Result<()> {
+    // 行末の空白や "\r" は比べない (最初の行をそのまま出力する)
+    Command::cargo_bin(PRG)?
+        .arg("-c")
+        .write_stdin("a \na\na\r\nb\n")
+        .assert()
+        .success()
+        .stdout("   3 a \n   1 b\n");
+    Command::cargo_bin(PRG)?
+        .arg("--global")
+        .write_stdin("a\r\nb\na\n")
+        .assert()
+        .success()
+        .stdout("a\r\nb\n");
+    Ok(())
+}
@@ -0,0 +1,780 @@
+use crate::FileInfo;
+use clap::ValueEnum;
+use memchr::memchr_iter;
+use rcommon::{Input, MyResult, BUF_SIZE};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
+
+/**
+ * 単語の区切り方
+ */
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum WordMode {
+    Whitespace, // 空白文字で区切られた並び (wc と同じ)
+    Unicode,    // UAX #29 の単語境界で区切られた、英数字を含む並び (日本語の文も単語に分かれる)
+}
+
+/**
+ * 行数とバイト数のほかに何を数えるか
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Scan {
+    pub text: bool,      // 単語数と文字数
+    pub width: bool,     // 表示幅 (最長の行の幅と全体の幅)
+    pub words: WordMode, // 単語の区切り方
+    pub graphemes: bool, // 書記素クラスタ (見た目の1文字) の数
+    pub stats: bool,     // 行ごとの統計 (--stats)
+}
+
+impl Scan {
+    pub const ALL: Scan = Scan {
+        text: true,
+        width: true,
+        words: WordMode::Whitespace,
+        graphemes: true,
+        stats: false,
+    };
+
+    /**
+     * UTF-8として読む必要があるかどうか (行数とバイト数だけなら memchr で済む)
+     */
+    fn decode(&self) -> bool {
+        self.text || self.width || self.segment() || self.stats
+    }
+
+    /**
+     * 行ごとに UAX #29 で区切る必要があるかどうか
+     *
+     * 単語の境界も書記素クラスタの境界も改行をまたがないので、1行ずつ区切ればよい
+     */
+    fn segment(&self) -> bool {
+        self.graphemes || (self.text && self.words == WordMode::Unicode)
+    }
+}
+
+/**
+ * 数え方の設定
+ */
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Options {
+    pub scan: Scan,
+    pub jobs: usize,     // 数えるスレッドの数
+    pub chunk_size: u64, // この大きさを超えるファイルは分割して並列に数える (表示幅を数える場合や、行ごとに区切る場合は分割しない)
+}
+
+/**
+ * 入力の一部を数えた結果
+ *
+ * 隣り合う部分の結果を合わせるときに、境界をまたぐ単語を二重に数えないよう、先頭と末尾が単語の途中かどうかも持つ
+ */
+#[derive(Debug, PartialEq)]
+pub(crate) struct Tally {
+    info: FileInfo,
+    starts_in_word: bool,
+    ends_in_word: bool,
+}
+
+impl Tally {
+    fn empty() -> Tally {
+        Tally {
+            info: FileInfo::default(),
+            starts_in_word: false,
+            ends_in_word: false,
+        }
+    }
+
+    /**
+     * 直後に続く部分の結果を合わせる
+     *
+     * 境界をまたぐ行の表示幅はタブの位置によって変わるので、表示幅を数える場合は分割しない。
+     * 行ごとの統計も同じく、行の途中で分割すると正しく合わせられないので分割しない
+     */
+    fn merge(self, next: Tally) -> Tally {
+        if self.info.num_bytes == 0 {
+            return next;
+        }
+        if next.info.num_bytes == 0 {
+            return self;
+        }
+        let joined = self.ends_in_word && next.starts_in_word;
+        let mut info = self.info;
+        info.add(&next.info);
+        info.num_words -= joined as usize;
+        Tally {
+            info,
+            starts_in_word: self.starts_in_word,
+            ends_in_word: next.ends_in_word,
+        }
+    }
+
+    pub fn into_info(self) -> FileInfo {
+        self.info
+    }
+}
+
+/**
+ * 読み込んだバイト列を順に受け取って数える
+ *
+ * 単語は空白文字 (char::is_whitespace) で区切られた並びとして数える。
+ * 不正なUTF-8のバイトは文字数には数えず、単語の一部として扱う (表示幅は0)。
+ * UAX #29 で区切る場合は、不正なバイトを U+FFFD に置き換えて1行ずつ区切る
+ */
+struct Counter {
+    tally: Tally,
+    scan: Scan,
+    line: String,         // UAX #29 で区切るために溜めている、現在の行
+    current: CurrentLine, // 行ごとの統計のための、現在の行の状態
+    started: bool,        // 最初の文字を見たかどうか
+    column: usize,        // 現在の行の表示幅
+    partial: [u8; 4],     // 前のバイト列の末尾で途切れたUTF-8の文字
+    partial_len: usize,
+}
+
+impl Counter {
+    fn new(scan: Scan) -> Counter {
+        Counter {
+            tally: Tally::empty(),
+            scan,
+            line: String::new(),
+            current: CurrentLine::new(),
+            started: false,
+            column: 0,
+            partial: [0; 4],
+            partial_len: 0,
+        }
+    }
+
+    fn feed(&mut self, data: &[u8]) {
+        self.tally.info.num_bytes += data.len();
+        self.tally.info.num_lines += memchr_iter(b'\n', data).count();
+        if !self.scan.decode() {
+            return;
+        }
+
+        // 前回途切れた文字を、今回のバイト列の先頭で補う
+        let mut data = data;
+        while self.partial_len > 0 && !data.is_empty() {
+            self.partial[self.partial_len] = data[0];
+            self.partial_len += 1;
+            data = &data[1..];
+
+            let (partial, partial_len) = (self.partial, self.partial_len);
+            match std::str::from_utf8(&partial[..partial_len]) {
+                Ok(s) => {
+                    self.partial_len = 0;
+                    self.text(s);
+                }
+                Err(e) => {
+                    if let Some(len) = e.error_len() {
+                        // 不正なバイト列だった場合は、残りのバイトを読み直す
+                        self.partial_len = 0;
+                        self.invalid();
+                        self.scan(&partial[len..partial_len]);
+                    }
+                }
+            }
+        }
+        self.scan(data);
+    }
+
+    /**
+     * 文字の途中から始まらないバイト列を数える
+     */
+    fn scan(&mut self, mut data: &[u8]) {
+        loop {
+            match std::str::from_utf8(data) {
+                Ok(s) => return self.text(s),
+                Err(e) => {
+                    let (valid, rest) = data.split_at(e.valid_up_to());
+                    self.text(std::str::from_utf8(valid).unwrap_or_default());
+                    match e.error_len() {
+                        Some(len) => {
+                            self.invalid();
+                            data = &rest[len..];
+                        }
+                        None => {
+                            // 末尾で途切れた文字は次のバイト列と合わせて数える
+                            self.partial[..rest.len()].copy_from_slice(rest);
+                            self.partial_len = rest.len();
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn text(&mut self, s: &str) {
+        if self.scan.segment() {
+            for piece in s.split_inclusive('\n') {
+                self.line.push_str(piece);
+                if piece.ends_with('\n') {
+                    self.segment_line();
+                }
+            }
+        }
+
+        if s.is_ascii() {
+            self.tally.info.num_chars += s.len();
+            for &b in s.as_bytes() {
+                self.step(matches!(b, b' ' | b'\t'..=b'\r'));
+                if self.scan.width {
+                    self.advance(b as char);
+                }
+                if self.scan.stats {
+                    self.stat(b as char);
+                }
+            }
+        } else {
+            for c in s.chars() {
+                self.tally.info.num_chars += 1;
+                self.step(c.is_whitespace());
+                if self.scan.width {
+                    self.advance(c);
+                }
+                if self.scan.stats {
+                    self.stat(c);
+                }
+            }
+        }
+    }
+
+    /**
+     * 表示幅を進める
+     *
+     * GNU wc -L と同じく、タブは次の8の倍数の桁まで進め、改行・復帰・改ページで行が終わる。
+     * 東アジアの全角文字は2桁、制御文字は0桁として数える
+     */
+    fn advance(&mut self, c: char) {
+        let width = match c {
+            '\n' | '\r' | '\x0c' => {
+                self.end_line();
+                return;
+            }
+            '\t' => 8 - self.column % 8,
+            c => c.width().unwrap_or(0),
+        };
+        self.column += width;
+        self.tally.info.width += width;
+    }
+
+    fn end_line(&mut self) {
+        let info = &mut self.tally.info;
+        info.max_line_length = info.max_line_length.max(self.column);
+        self.column = 0;
+    }
+
+    /**
+     * 不正なUTF-8のバイト (列) を1つ数える
+     */
+    fn invalid(&mut self) {
+        self.step(false);
+        if self.scan.segment() {
+            self.line.push(char::REPLACEMENT_CHARACTER);
+        }
+        if self.scan.stats {
+            self.stat(char::REPLACEMENT_CHARACTER);
+        }
+    }
+
+    /**
+     * 行ごとの統計のために1文字進める
+     */
+    fn stat(&mut self, c: char) {
+        let current = &mut self.current;
+        if c != '\n' {
+            current.push(c);
+            return;
+        }
+        let stats = &mut self.tally.info.stats;
+        let crlf = current.pending_cr;
+        let len = current.len - crlf as usize;
+        stats.add_line(len, current.blank, len > 0 && current.last_space);
+        stats.add_ending(crlf);
+        *current = CurrentLine::new();
+    }
+
+    /**
+     * 溜めた行を UAX #29 で区切って数える
+     */
+    fn segment_line(&mut self) {
+        let info = &mut self.tally.info;
+        if self.scan.words == WordMode::Unicode {
+            info.num_words += self.line.unicode_words().count();
+        }
+        if self.scan.graphemes {
+            info.num_graphemes += self.line.graphemes(true).count();
+        }
+        self.line.clear();
+    }
+
+    /**
+     * 1文字進める
+     */
+    fn step(&mut self, space: bool) {
+        if !self.started {
+            self.started = true;
+            self.tally.starts_in_word = !space;
+        }
+        if space {
+            self.tally.ends_in_word = false;
+        } else if !self.tally.ends_in_word {
+            self.tally.ends_in_word = true;
+            if self.scan.words == WordMode::Whitespace {
+                self.tally.info.num_words += 1;
+            }
+        }
+    }
+
+    fn finish(mut self) -> Tally {
+        // 入力の末尾で途切れた文字は不正なバイト列として扱う
+        if self.partial_len > 0 {
+            self.partial_len = 0;
+            self.invalid();
+        }
+        // 改行で終わらない最後の行
+        self.end_line();
+        self.segment_line();
+        if self.scan.stats && self.current.len > 0 {
+            let current = &self.current;
+            let trailing = current.last_space || current.pending_cr;
+            let stats = &mut self.tally.info.stats;
+            stats.add_line(current.len, current.blank, trailing);
+            stats.set_missing_final_newline();
+        }
+        self.tally
+    }
+}
+
+/**
+ * 行ごとの統計のために覚えておく、現在の行の状態
+ *
+ * CR は直後に LF が来れば改行の一部、そうでなければ行の中の空白文字として扱う
+ */
+struct CurrentLine {
+    len: usize,       // これまでの文字数 (保留中の CR を含む)
+    pending_cr: bool, // 最後の文字が CR かどうか
+    last_space: bool, // 保留中の CR を除いた最後の文字が空白文字かどうか
+    blank: bool,      // 空白文字だけかどうか
+}
+
+impl CurrentLine {
+    fn new() -> CurrentLine {
+        CurrentLine {
+            len: 0,
+            pending_cr: false,
+            last_space: false,
+            blank: true,
+        }
+    }
+
+    fn push(&mut self, c: char) {
+        if self.pending_cr {
+            self.pending_cr = false;
+            self.last_space = true;
+        }
+        self.len += 1;
+        if c == '\r' {
+            self.pending_cr = true;
+        } else {
+            self.last_space = c.is_whitespace();
+            self.blank &= self.last_space;
+        }
+    }
+}
+
+/**
+ * 入力を最後まで読んで数える
+ */
+pub(crate) fn count_reader(mut reader: impl BufRead, scan: Scan) -> io::Result<Tally> {
+    let mut counter = Counter::new(scan);
+    loop {
+        let buf = match reader.fill_buf() {
+            Ok(buf) => buf,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if buf.is_empty() {
+            break;
+        }
+        counter.feed(buf);
+        let len = buf.len();
+        reader.consume(len);
+    }
+    Ok(counter.finish())
+}
+
+/**
+ * 並列に数える仕事の単位 (ファイル全体か、大きなファイルの一部)
+ */
+struct Job<'a> {
+    filename: &'a Path,
+    range: Option<Range<u64>>,
+}
+
+impl Job<'_> {
+    fn run(&self, scan: Scan) -> io::Result<Tally> {
+        match &self.range {
+            None => {
+                let mut stdin = io::empty(); // "-" は仕事にしないので使わない
+                let input = Input::open(self.filename, &mut stdin)?;
+                count_reader(input.into_reader(), scan)
+            }
+            Some(range) => {
+                let mut file = File::open(self.filename)?;
+                let (start, end) = if scan.decode() {
+                    (
+                        char_boundary(&mut file, range.start)?,
+                        char_boundary(&mut file, range.end)?,
+                    )
+                } else {
+                    (range.start, range.end)
+                };
+                file.seek(SeekFrom::Start(start))?;
+                let reader = BufReader::with_capacity(BUF_SIZE, file.take(end - start));
+                count_reader(reader, scan)
+            }
+        }
+    }
+}
+
+/**
+ * pos 以降で最初の文字の先頭の位置
+ *
+ * 分割した部分の両側で同じ位置を使うので、UTF-8の文字が2つの部分に分かれることはない
+ */
+fn char_boundary(file: &mut File, pos: u64) -> io::Result<u64> {
+    if pos == 0 {
+        return Ok(0);
+    }
+    file.seek(SeekFrom::Start(pos))?;
+    let mut buf = Vec::with_capacity(3);
+    file.take(3).read_to_end(&mut buf)?;
+    let skip = buf.iter().take_while(|&&b| b & 0xc0 == 0x80).count();
+    Ok(pos + skip as u64)
+}
+
+/**
+ * 各ファイルを数え、ファイルの順に each を呼び出す
+ *
+ * jobs が2以上の場合は、ファイルや大きなファイルの一部をスレッドで並列に数える。
+ * 標準入力("-")は分割できないので、呼び出し元のスレッドで数える
+ */
+pub(crate) fn count_files<F>(
+    files: &[PathBuf],
+    options: Options,
+    stdin: &mut dyn BufRead,
+    mut each: F,
+) -> MyResult<()>
+where
+    F: FnMut(&Path, io::Result<FileInfo>) -> MyResult<()>,
+{
+    if options.jobs <= 1 {
+        for filename in files {
+            let result = Input::open(filename, &mut *stdin)
+                .and_then(|input| count_reader(input.into_reader(), options.scan));
+            each(filename, result.map(Tally::into_info))?;
+        }
+        return Ok(());
+    }
+
+    let chunk_size = options.chunk_size.max(4);
+    let mut jobs = vec![];
+    let mut spans = vec![];
+    for filename in files {
+        let first = jobs.len();
+        if filename.as_os_str() != "-" {
+            match fs::metadata(filename) {
+                Ok(meta)
+                    if meta.is_file()
+                        && meta.len() > chunk_size
+                        && !options.scan.width
+                        && !options.scan.segment()
+                        && !options.scan.stats =>
+                {
+                    let mut start = 0;
+                    while start < meta.len() {
+                        let end = (start + chunk_size).min(meta.len());
+                        jobs.push(Job {
+                            filename,
+                            range: Some(start..end),
+                        });
+                        start = end;
+                    }
+                }
+                _ => jobs.push(Job {
+                    filename,
+                    range: None,
+                }),
+            }
+        }
+        spans.push(first..jobs.len());
+    }
+
+    let scan = options.scan;
+    let next = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..options.jobs.min(jobs.len()) {
+            let tx = tx.clone();
+            let (jobs, next) = (&jobs, &next);
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(job) = jobs.get(index) else { break };
+                // 受け取る側が終わっていれば、残りの仕事はしない
+                if tx.send((index, job.run(scan))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+
+        // 終わった順に届く結果を、ファイルの順に並べ直す
+        let mut done = HashMap::new();
+        let mut wait = |index: usize| loop {
+            if let Some(result) = done.remove(&index) {
+                return result;
+            }
+            match rx.recv() {
+                Ok((finished, result)) => {
+                    done.insert(finished, result);
+                }
+                Err(_) => return Err(io::Error::other("worker thread exited")),
+            }
+        };
+
+        let result = files.iter().zip(spans).try_for_each(|(filename, span)| {
+            let result = if filename.as_os_str() == "-" {
+                Input::open(filename, &mut *stdin)
+                    .and_then(|input| count_reader(input.into_reader(), scan))
+            } else {
+                span.map(&mut wait)
+                    .try_fold(Tally::empty(), |tally, part| Ok(tally.merge(part?)))
+            };
+            each(filename, result.map(Tally::into_info))
+        });
+
+        // 途中で終わった場合は、残りの仕事を取らせない
+        next.store(jobs.len(), Ordering::Relaxed);
+        result
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{count_files, count_reader, Counter, Options, Scan, Tally, WordMode};
+    use crate::stats::LineStats;
+    use std::io;
+    use std::path::PathBuf;
+
+    const WORDS: Scan = Scan {
+        text: true,
+        width: false,
+        words: WordMode::Whitespace,
+        graphemes: false,
+        stats: false,
+    };
+
+    // マルチバイト文字、全角スペース、不正なバイト列を含む入力
+    const TEXT: &[u8] = b"Hello, w\xc3\xb6rld!\n\xe3\x81\x82\xe3\x80\x80\xe3\x81\x84 caf\xe9 \xf0\x9f\xa6\x80\n\n  tail";
+
+    fn tally(data: &[u8]) -> Tally {
+        count_reader(data, Scan::ALL).unwrap()
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let info = tally(b"caf\xe9 ok\n").into_info();
+        assert_eq!(info.num_lines, 1);
+        assert_eq!(info.num_words, 2);
+        assert_eq!(info.num_bytes, 8);
+        assert_eq!(info.num_chars, 7);
+
+        // 末尾で途切れた文字は文字数に数えない
+        let info = tally(b"ok \xe3\x81").into_info();
+        assert_eq!(info.num_words, 2);
+        assert_eq!(info.num_chars, 3);
+    }
+
+    #[test]
+    fn test_feed_split() {
+        let whole = tally(TEXT);
+        assert_eq!(whole.info.num_words, 7);
+        assert_eq!(whole.info.num_chars, 31);
+
+        // どこで区切って渡しても同じ結果になる
+        for i in 0..=TEXT.len() {
+            let mut counter = Counter::new(Scan::ALL);
+            counter.feed(&TEXT[..i]);
+            counter.feed(&TEXT[i..]);
+            assert_eq!(counter.finish(), whole, "split at {}", i);
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let whole = count_reader(TEXT, WORDS).unwrap();
+        for i in 0..=TEXT.len() {
+            // 文字の途中では分割しない
+            if TEXT.get(i).is_some_and(|b| b & 0xc0 == 0x80) {
+                continue;
+            }
+            let count = |data| count_reader(data, WORDS).unwrap();
+            let merged = count(&TEXT[..i]).merge(count(&TEXT[i..]));
+            assert_eq!(merged, whole, "split at {}", i);
+        }
+    }
+
+    #[test]
+    fn test_width() {
+        let width = |data: &[u8]| {
+            let info = tally(data).into_info();
+            (info.max_line_length, info.width)
+        };
+        assert_eq!(width(b""), (0, 0));
+        assert_eq!(width(b"ab"), (2, 2));
+        assert_eq!(width(b"a\tb\n"), (9, 9));
+        assert_eq!(width(b"1234567\t\n12345678\t\n"), (16, 24));
+        assert_eq!(width("あいう\nｱｲ\n".as_bytes()), (6, 8));
+        assert_eq!(width(b"abc\rxy\n"), (3, 5));
+        assert_eq!(width(b"a\xff\x01b\n"), (2, 2));
+        assert_eq!(width(TEXT), (13, 32));
+    }
+
+    #[test]
+    fn test_segment() {
+        let scan = Scan {
+            words: WordMode::Unicode,
+            ..Scan::ALL
+        };
+        let text = "日本語の文章です。\nHello, world! e\u{301} 👨\u{200d}👩\u{200d}👧\r\n";
+        let info = count_reader(text.as_bytes(), scan).unwrap().into_info();
+        assert_eq!(info.num_words, 11);
+        assert_eq!(info.num_chars, 34);
+        assert_eq!(info.num_graphemes, 28);
+
+        // 空白区切りの単語数はそのまま
+        let info = tally(text.as_bytes()).into_info();
+        assert_eq!(info.num_words, 5);
+        assert_eq!(info.num_graphemes, 28);
+
+        // 不正なバイトは U+FFFD として1つの書記素クラスタに数える
+        let info = count_reader(&b"caf\xe9 ok\n\xff"[..], scan)
+            .unwrap()
+            .into_info();
+        assert_eq!(info.num_words, 2);
+        assert_eq!(info.num_graphemes, 9);
+    }
+
+    #[test]
+    fn test_segment_split() {
+        // 行や文字の途中で区切られたバイト列でも、まとめて数えた場合と同じになる
+        let scan = Scan {
+            words: WordMode::Unicode,
+            ..Scan::ALL
+        };
+        let text = "e\u{301}\u{301} テスト\r\nmore words\n".as_bytes();
+        let expected = count_reader(text, scan).unwrap();
+        for split in 1..text.len() {
+            let mut counter = Counter::new(scan);
+            counter.feed(&text[..split]);
+            counter.feed(&text[split..]);
+            assert_eq!(counter.finish(), expected);
+        }
+    }
+
+    #[test]
+    fn test_stats() {
+        let scan = Scan {
+            stats: true,
+            ..WORDS
+        };
+        let text = b"a  \r\n\n  \n\xff\xe6\x97\xa5\nlast\r";
+        let stats = count_reader(&text[..], scan).unwrap().into_info().stats;
+        let mut expected = LineStats::default();
+        for (len, blank, trailing) in [(3, false, true), (0, true, false), (2, true, true)] {
+            expected.add_line(len, blank, trailing);
+        }
+        expected.add_ending(true);
+        expected.add_ending(false);
+        expected.add_ending(false);
+        expected.add_line(2, false, false);
+        expected.add_ending(false);
+        expected.add_line(5, false, true);
+        expected.set_missing_final_newline();
+        assert_eq!(stats, expected);
+
+        // CR と LF の間で区切られても CRLF として数える
+        for split in 1..text.len() {
+            let mut counter = Counter::new(scan);
+            counter.feed(&text[..split]);
+            counter.feed(&text[split..]);
+            assert_eq!(counter.finish().into_info().stats, expected);
+        }
+    }
+
+    #[test]
+    fn test_lines_and_bytes_only() {
+        let scan = Scan {
+            text: false,
+            width: false,
+            graphemes: false,
+            ..WORDS
+        };
+        let info = count_reader(TEXT, scan).unwrap().into_info();
+        assert_eq!(info.num_lines, 3);
+        assert_eq!(info.num_bytes, TEXT.len());
+        assert_eq!(info.num_words, 0);
+    }
+
+    #[test]
+    fn test_count_files_parallel() {
+        let files: Vec<PathBuf> = [
+            "tests/inputs/atlamal.txt",
+            "-",
+            "no-such-file",
+            "tests/inputs/fox.txt",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+
+        let collect = |options: Options| {
+            let mut results = vec![];
+            count_files(
+                &files,
+                options,
+                &mut io::Cursor::new(TEXT),
+                |filename, result| {
+                    results.push((filename.to_owned(), result.map_err(|e| e.to_string())));
+                    Ok(())
+                },
+            )
+            .unwrap();
+            results
+        };
+
+        let sequential = collect(Options {
+            scan: WORDS,
+            jobs: 1,
+            chunk_size: 1,
+        });
+        assert!(sequential[2].1.is_err());
+        for chunk_size in [4, 7, 64] {
+            let parallel = collect(Options {
+                scan: WORDS,
+                jobs: 3,
+                chunk_size,
+            });
+            assert_eq!(parallel, sequential, "chunk size {}", chunk_size);
+        }
+    }
+}
@@ -1,212 +1,663 @@
-use clap::{App, Arg};
-use std::error::Error;
-use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use clap::{Parser, ValueEnum};
+use counter::{Options, Scan};
+use list::FileList;
+use rcommon::cli::GenerateArgs;
+use rcommon::{MyResult, Reporter};
+use record::{Record, RecordWriter};
+use stats::LineStats;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, BufRead, ErrorKind, Write};
+use std::path::PathBuf;
+use std::thread;
 
-type MyResult<T> = Result<T, Box<dyn Error>>;
+mod counter;
+mod list;
+mod record;
+mod stats;
+
+pub use counter::WordMode;
+pub use record::Format;
+
+// --jobs を指定したときに、これより大きいファイルは分割して並列に数える
+const CHUNK_SIZE: u64 = 64 * 1024 * 1024;
+
+/**
+ * 合計の行を出力するかどうか
+ */
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Total {
+    Auto,   // ファイルを2つ以上指定した場合だけ
+    Always, // ファイルが1つでも出力する
+    Only,   // 合計だけを出力する ("total" の名前は付けない)
+    Never,
+}
 
 #[derive(Debug)]
 pub struct Config {
-    files: Vec<String>,
+    files: Vec<PathBuf>,         // 空の場合は、名前を付けずに標準入力を数える
+    file_list: Option<FileList>, // 指定した場合は files の代わりに、一覧から読んだファイルを数える
     lines: bool,
     words: bool,
     bytes: bool,
     chars: bool,
+    graphemes: bool,       // 書記素クラスタ (見た目の1文字) の数
+    word_mode: WordMode,   // 単語の区切り方
+    max_line_length: bool, // 最長の行の表示幅
+    width: bool,           // 全体の表示幅
+    stats: bool,           // 行ごとの統計
+    format: Format,
+    total: Total,
+    stdin_len: Option<u64>, // 標準入力が通常のファイルの場合はそのサイズ (桁数の計算に使う)
+    jobs: usize,            // 数えるスレッドの数
+    chunk_size: u64,        // 並列に数えるときに、ファイルを分割する大きさ
+}
+
+impl Config {
+    /**
+     * 数える項目を何も指定しない場合は、行数・単語数・バイト数を出力する
+     */
+    pub fn new(files: Vec<String>) -> Config {
+        Config {
+            files: files.into_iter().map(PathBuf::from).collect(),
+            file_list: None,
+            lines: false,
+            words: false,
+            bytes: false,
+            chars: false,
+            graphemes: false,
+            word_mode: WordMode::Whitespace,
+            max_line_length: false,
+            width: false,
+            stats: false,
+            format: Format::Text,
+            total: Total::Auto,
+            stdin_len: None,
+            jobs: 1,
+            chunk_size: CHUNK_SIZE,
+        }
+    }
+
+    /**
+     * NUL 区切りのファイル名の一覧から、数えるファイルを読み込む ("-" の場合は標準入力)
+     */
+    pub fn files0_from(mut self, path: &str) -> Config {
+        self.file_list = Some(FileList {
+            path: path.to_string(),
+            delimiter: b'\0',
+        });
+        self
+    }
+
+    /**
+     * 改行区切りのファイル名の一覧から、数えるファイルを読み込む ("-" の場合は標準入力)
+     */
+    pub fn files_from(mut self, path: &str) -> Config {
+        self.file_list = Some(FileList {
+            path: path.to_string(),
+            delimiter: b'\n',
+        });
+        self
+    }
+
+    pub fn lines(mut self, lines: bool) -> Config {
+        self.lines = lines;
+        self
+    }
+
+    pub fn words(mut self, words: bool) -> Config {
+        self.words = words;
+        self
+    }
+
+    pub fn bytes(mut self, bytes: bool) -> Config {
+        self.bytes = bytes;
+        self
+    }
+
+    pub fn chars(mut self, chars: bool) -> Config {
+        self.chars = chars;
+        self
+    }
+
+    /**
+     * 書記素クラスタ (結合文字や絵文字の並びを1つと数える、見た目の1文字) の数
+     */
+    pub fn graphemes(mut self, graphemes: bool) -> Config {
+        self.graphemes = graphemes;
+        self
+    }
+
+    /**
+     * 単語の区切り方 (Unicode の場合は UAX #29 の単語境界で区切る)
+     */
+    pub fn word_mode(mut self, word_mode: WordMode) -> Config {
+        self.word_mode = word_mode;
+        self
+    }
+
+    /**
+     * 最長の行の表示幅 (全角文字は2桁、タブは次の8の倍数の桁まで)
+     */
+    pub fn max_line_length(mut self, max_line_length: bool) -> Config {
+        self.max_line_length = max_line_length;
+        self
+    }
+
+    /**
+     * 入力全体の表示幅
+     */
+    pub fn width(mut self, width: bool) -> Config {
+        self.width = width;
+        self
+    }
+
+    /**
+     * 各ファイルの数のあとに、行の長さの最小・最大・平均・中央値とヒストグラム、
+     * 空行・行末に空白がある行・改行コードの数、最後に改行がないかどうかを出力する
+     *
+     * Text の形式でだけ出力する
+     */
+    pub fn stats(mut self, stats: bool) -> Config {
+        self.stats = stats;
+        self
+    }
+
+    /**
+     * 出力形式
+     *
+     * Text 以外の形式では、指定した項目にかかわらず全ての数を出力する
+     */
+    pub fn format(mut self, format: Format) -> Config {
+        self.format = format;
+        self
+    }
+
+    /**
+     * 合計の行を出力する条件
+     *
+     * Text 以外の形式では、Auto でも合計のレコードを出力する
+     */
+    pub fn total(mut self, total: Total) -> Config {
+        self.total = total;
+        self
+    }
+
+    /**
+     * 複数のファイルや大きなファイルの一部を、jobs 個のスレッドで並列に数える
+     *
+     * 0 の場合は CPU の数だけスレッドを使う
+     */
+    pub fn jobs(mut self, jobs: usize) -> Config {
+        self.jobs = jobs;
+        self
+    }
+
+    /**
+     * 並列に数えるときに、ファイルを分割する大きさ
+     */
+    pub fn chunk_size(mut self, chunk_size: u64) -> Config {
+        self.chunk_size = chunk_size;
+        self
+    }
 }
 
 /**
  * ファイル情報
  */
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Default, PartialEq)]
 pub struct FileInfo {
     num_lines: usize,
     num_words: usize,
     num_bytes: usize,
     num_chars: usize,
+    num_graphemes: usize,
+    max_line_length: usize, // 最長の行の表示幅
+    width: usize,           // 全ての行の表示幅の合計
+    stats: LineStats,       // 行ごとの統計 (--stats の場合だけ数える)
+}
+
+impl FileInfo {
+    /**
+     * 合計に加える
+     */
+    fn add(&mut self, other: &FileInfo) {
+        self.num_lines += other.num_lines;
+        self.num_words += other.num_words;
+        self.num_bytes += other.num_bytes;
+        self.num_chars += other.num_chars;
+        self.num_graphemes += other.num_graphemes;
+        // 最長の行は合計ではなく、全てのファイルの中で最長のもの
+        self.max_line_length = self.max_line_length.max(other.max_line_length);
+        self.width += other.width;
+        self.stats.merge(&other.stats);
+    }
+}
+
+// コマンドライン引数の定義 (ヘルプ・補完スクリプト・manページにも使われる)
+#[derive(Debug, Parser)]
+#[command(
+    name = "wcr",
+    version = "0.1.0",
+    author = "hoge",
+    about = "Rust wc"
+)]
+struct Args {
+    /// Input file(s) [default: standard input]
+    #[arg(value_name = "FILE")]
+    files: Vec<String>,
+
+    /// Read NUL-separated file names from F ("-" for standard input)
+    #[arg(long = "files0-from", value_name = "F", conflicts_with = "files")]
+    files0_from: Option<String>,
+
+    /// Read newline-separated file names from F ("-" for standard input)
+    #[arg(
+        long = "files-from",
+        value_name = "F",
+        conflicts_with_all = ["files", "files0_from"]
+    )]
+    files_from: Option<String>,
+
+    /// Show byte count
+    #[arg(short = 'c', long = "bytes")]
+    bytes: bool,
+
+    /// Show character count
+    #[arg(short = 'm', long = "chars")]
+    chars: bool,
+
+    /// Show line count
+    #[arg(short = 'l', long = "lines")]
+    lines: bool,
+
+    /// Show word count, splitting words at whitespace or at Unicode (UAX #29) word boundaries
+    #[arg(
+        short = 'w',
+        long = "words",
+        value_name = "MODE",
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "whitespace",
+        overrides_with = "words"
+    )]
+    words: Option<WordMode>,
+
+    /// Show grapheme cluster (user-perceived character) count
+    #[arg(long = "graphemes")]
+    graphemes: bool,
+
+    /// Show the display width of the longest line
+    #[arg(short = 'L', long = "max-line-length")]
+    max_line_length: bool,
+
+    /// Show the display width of the whole input
+    #[arg(long = "width")]
+    width: bool,
+
+    /// Show per-line statistics and a histogram of line lengths
+    #[arg(long = "stats", conflicts_with = "format")]
+    stats: bool,
+
+    /// Output format (json, csv and tsv print every count for each file)
+    #[arg(long = "format", value_name = "FORMAT", value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// When to print a line with total counts
+    #[arg(long = "total", value_name = "WHEN", value_enum, default_value_t = Total::Auto)]
+    total: Total,
+
+    /// Count files and chunks of large files on N threads (0 = one per CPU)
+    #[arg(short = 'j', long = "jobs", value_name = "N", default_value_t = 1)]
+    jobs: usize,
+
+    #[command(flatten)]
+    generate: GenerateArgs,
 }
 
 pub fn get_args() -> MyResult<Config> {
-    let matches = App::new("wcr")
-        .version("0.1.0")
-        .author("hoge")
-        .about("Rust wc")
-        .arg(
-            Arg::with_name("files")
-                .value_name("FILE")
-                .help("Input file(s)")
-                .multiple(true)
-                .default_value("-")
-        )
-        .arg(
-            Arg::with_name("bytes")
-                .short("c")
-                .long("bytes")
-                .conflicts_with("chars")
-                .help("Show byte count")
-                .takes_value(false)
-        )
-        .arg(
-            Arg::with_name("chars")
-                .short("m")
-                .long("chars")
-                .help("Show character count")
-                .takes_value(false)
-        )
-        .arg(
-            Arg::with_name("lines")
-                .short("l")
-                .long("lines")
-                .help("Show line count")
-                .default_value("true")
-                .takes_value(false)
-        )
-        .arg(
-            Arg::with_name("words")
-                .short("w")
-                .long("words")
-                .help("Show word count")
-                .takes_value(false)
-        )
-        .get_matches();
-
-        let mut lines = matches.is_present("lines");
-        let mut words = matches.is_present("words");
-        let mut bytes = matches.is_present("bytes");
-        let chars = matches.is_present("chars");
-
-        // 全てのフラグがfalseの場合はlines,words,bytesをtrueにする
-        if [lines, words, bytes, chars].iter().all(|v| v == &false) {
-            lines = true;
-            words = true;
-            bytes = true;
-        }
+    get_args_from(std::env::args_os())
+}
 
-        Ok(Config {
-            files: matches.values_of_lossy("files").unwrap_or_default(),
-            lines,
-            words,
-            bytes,
-            chars,
-        })
+/**
+ * 指定した引数列を解析し、Config構造体を返す
+ *
+ * 先頭の要素はプログラム名として扱われる
+ */
+pub fn get_args_from<I, T>(args: I) -> MyResult<Config>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let args = Args::parse_from(args);
+    args.generate.exit_if_requested::<Args>();
+
+    // 全てのフラグがfalseの場合は run_with で lines,words,bytes をtrueにする
+    let mut config = Config::new(args.files);
+    if let Some(path) = &args.files0_from {
+        config = config.files0_from(path);
+    }
+    if let Some(path) = &args.files_from {
+        config = config.files_from(path);
+    }
+    Ok(config
+        .lines(args.lines)
+        .words(args.words.is_some())
+        .word_mode(args.words.unwrap_or(WordMode::Whitespace))
+        .bytes(args.bytes)
+        .chars(args.chars)
+        .graphemes(args.graphemes)
+        .max_line_length(args.max_line_length)
+        .width(args.width)
+        .stats(args.stats)
+        .format(args.format)
+        .total(args.total)
+        .jobs(args.jobs))
 }
 
-pub fn run(config: Config) -> MyResult<()> {
+pub fn run(mut config: Config) -> MyResult<()> {
+    config.stdin_len = stdin_len();
+    run_with(
+        config,
+        &mut io::stdin().lock(),
+        &mut io::stdout().lock(),
+        &mut io::stderr(),
+    )
+}
 
+/**
+ * 標準入力・標準出力・標準エラーの代わりに、指定したリーダーとライターを使って実行する
+ */
+pub fn run_with(
+    mut config: Config,
+    stdin: &mut dyn BufRead,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> MyResult<()> {
+    let mut reporter = Reporter::new("wcr", err);
 
-    let mut total_lines = 0;
-    let mut total_words = 0;
-    let mut total_bytes = 0;
-    let mut total_chars = 0;
+    // ファイルを指定しない場合は標準入力を数えるが、"-" と違って名前は出力しない
+    let mut unnamed_stdin = false;
+    if let Some(list) = &config.file_list {
+        config.files = list.read(stdin, &mut reporter)?;
+    } else if config.files.is_empty() {
+        config.files.push(PathBuf::from("-"));
+        unnamed_stdin = true;
+    }
 
-    for filename in &config.files {
-        match open(filename) {
-            Err(err) => eprintln!("{}: {}", filename, err),
-            Ok(file) => {
-                if let Ok(info) = count(file) {
-                    println!(
-                        "{}{}{}{}{}",
-                        format_field(info.num_lines, config.lines),
-                        format_field(info.num_words, config.words),
-                        format_field(info.num_bytes, config.bytes),
-                        format_field(info.num_chars, config.chars),
-                        if filename == "-" {
-                            "".to_string()
-                        } else {
-                            format!(" {}", filename)
-                        }
-                    );
-
-                    total_lines += info.num_lines;
-                    total_words += info.num_words;
-                    total_bytes += info.num_bytes;
-                    total_chars += info.num_chars;
+    // 全てのフラグがfalseの場合はlines,words,bytesをtrueにする
+    if !(config.lines
+        || config.words
+        || config.bytes
+        || config.chars
+        || config.graphemes
+        || config.max_line_length
+        || config.width)
+    {
+        config.lines = true;
+        config.words = true;
+        config.bytes = true;
+    }
+
+    let jobs = match config.jobs {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        jobs => jobs,
+    };
+    let scan = match config.format {
+        Format::Text => Scan {
+            text: config.words || config.chars,
+            width: config.max_line_length || config.width,
+            words: config.word_mode,
+            graphemes: config.graphemes,
+            stats: config.stats,
+        },
+        _ => Scan {
+            words: config.word_mode,
+            ..Scan::ALL
+        },
+    };
+    let options = Options {
+        scan,
+        jobs,
+        chunk_size: config.chunk_size,
+    };
+
+    match config.format {
+        Format::Text => {
+            let width = number_width(&config);
+            let table = Table {
+                config: &config,
+                width,
+                unnamed_stdin,
+            };
+            table.write(options, stdin, out, &mut reporter)?
+        }
+        format => {
+            let records = RecordWriter::new(format, out);
+            write_records(&config, options, stdin, records, &mut reporter)?
+        }
+    }
+    reporter.finish()
+}
+
+/**
+ * wc と同じ形式の表
+ */
+struct Table<'a> {
+    config: &'a Config,
+    width: usize,        // 数を右寄せにする桁数
+    unnamed_stdin: bool, // ファイルを指定せずに標準入力を数える場合は名前を出力しない
+}
+
+impl Table<'_> {
+    /**
+     * ファイルごとの数と合計を出力する
+     *
+     * ディレクトリは GNU wc と同じく、エラーを表示したうえで 0 の行を出力する
+     */
+    fn write(
+        &self,
+        options: Options,
+        stdin: &mut dyn BufRead,
+        out: &mut dyn Write,
+        reporter: &mut Reporter,
+    ) -> MyResult<()> {
+        let files = &self.config.files;
+        let mut total = FileInfo::default();
+        let mut counted = 0; // 数えられたファイルの数 (統計の合計に使う)
+        counter::count_files(files, options, stdin, |filename, result| {
+            let filename = filename.display().to_string();
+            let info = match result {
+                Ok(info) => info,
+                Err(err) => {
+                    let is_dir = err.kind() == ErrorKind::IsADirectory;
+                    reporter.error(&filename, err);
+                    if !is_dir {
+                        return Ok(());
+                    }
+                    FileInfo::default()
+                }
+            };
+            if self.config.total != Total::Only {
+                let name = (!self.unnamed_stdin).then_some(filename.as_str());
+                self.write_info(&info, &mut *out, name)?;
+                if self.config.stats {
+                    info.stats.write_report(&mut *out, 1)?;
                 }
             }
+            total.add(&info);
+            counted += 1;
+            Ok(())
+        })?;
+
+        let show_total = match self.config.total {
+            Total::Auto => files.len() > 1,
+            Total::Always | Total::Only => true,
+            Total::Never => false,
+        };
+        if show_total {
+            let name = (self.config.total != Total::Only).then_some("total");
+            self.write_info(&total, &mut *out, name)?;
+            if self.config.stats {
+                total.stats.write_report(out, counted)?;
+            }
         }
+        Ok(())
     }
 
-    if config.files.len() > 1 {
-        println!(
-            "{}{}{}{} total",
-            format_field(total_lines, config.lines),
-            format_field(total_words, config.words),
-            format_field(total_bytes, config.bytes),
-            format_field(total_chars, config.chars)
-        );
+    /**
+     * 1つのファイル (または合計) の数を、指定した項目だけ1行で出力する
+     */
+    fn write_info(&self, info: &FileInfo, out: &mut dyn Write, name: Option<&str>) -> MyResult<()> {
+        let config = self.config;
+        let fields = [
+            (info.num_lines, config.lines),
+            (info.num_words, config.words),
+            (info.num_chars, config.chars),
+            (info.num_graphemes, config.graphemes),
+            (info.num_bytes, config.bytes),
+            (info.max_line_length, config.max_line_length),
+            (info.width, config.width),
+        ];
+        let line: Vec<String> = fields
+            .iter()
+            .filter(|(_, show)| *show)
+            .map(|(value, _)| format_field(*value, self.width))
+            .chain(name.map(|name| name.to_string()))
+            .collect();
+        writeln!(out, "{}", line.join(" "))?;
+        Ok(())
     }
-    Ok(())
 }
 
 /**
- * ファイルオープン
+ * ファイルごとのレコードと合計のレコードを出力する
+ *
+ * エラーはレコードに含めたうえで、標準エラーにも表示する。
+ * --total=only の場合はファイルごとのレコードを、--total=never の場合は合計のレコードを出力しない
  */
-fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+fn write_records(
+    config: &Config,
+    options: Options,
+    stdin: &mut dyn BufRead,
+    mut records: RecordWriter,
+    reporter: &mut Reporter,
+) -> MyResult<()> {
+    let mut total = FileInfo::default();
+    counter::count_files(&config.files, options, stdin, |filename, result| {
+        let filename = filename.display().to_string();
+        if config.total != Total::Only {
+            let info = result.as_ref().map_err(|e| e.to_string());
+            records.write(&Record::file(&filename, info))?;
+        }
+        match result {
+            Err(err) => reporter.error(&filename, err),
+            Ok(info) => total.add(&info),
+        }
+        Ok(())
+    })?;
+
+    if config.total != Total::Never {
+        records.write(&Record::total(&total))?;
     }
+    records.finish()
 }
 
 /**
  * 出力結果フォーマット
  */
-fn format_field(value: usize, show: bool) -> String {
-    if show {
-        format!("{:>8}", value)
-    } else {
-        "".to_string()
-    }
+fn format_field(value: usize, width: usize) -> String {
+    format!("{:>width$}", value)
 }
 
 /**
- * ファイルの要素をカウント
- * 
- * 引数 impl BufRead は、BufRead トレイトを実装している型を受け取ることを示す
+ * GNU wc と同じ方法で、数を右寄せにする桁数を決める
+ *
+ * 通常のファイルのサイズの合計の桁数にする。パイプなどサイズが分からない入力を含む場合は最低7桁。
+ * ファイルも項目も1つだけの場合や、ファイルの一覧を標準入力から読んだ場合は揃えない
  */
-pub fn count(mut file: impl BufRead) -> MyResult<FileInfo> {
-    let mut num_lines = 0;
-    let mut num_words = 0;
-    let mut num_bytes = 0;
-    let mut num_chars = 0;
-
-    let mut line = String::new();
-
-    loop {
-        let line_bytes = file.read_line(&mut line)?;
-        if line_bytes == 0 {
-            break;
+fn number_width(config: &Config) -> usize {
+    let columns = [
+        config.lines,
+        config.words,
+        config.chars,
+        config.graphemes,
+        config.bytes,
+        config.max_line_length,
+        config.width,
+    ]
+    .iter()
+    .filter(|show| **show)
+    .count();
+    let streamed = config
+        .file_list
+        .as_ref()
+        .is_some_and(|list| list.path == "-");
+    if config.files.is_empty() || streamed || (config.files.len() == 1 && columns == 1) {
+        return 1;
+    }
+
+    let mut minimum_width = 1;
+    let mut regular_total = 0;
+    for filename in &config.files {
+        let len = if filename.as_os_str() == "-" {
+            Ok(config.stdin_len)
+        } else {
+            fs::metadata(filename).map(|meta| meta.is_file().then_some(meta.len()))
+        };
+        match len {
+            Ok(Some(len)) => regular_total += len,
+            Ok(None) => minimum_width = 7,
+            // 開けないファイルは桁数に影響しない
+            Err(_) => {}
         }
-        num_bytes += line_bytes;
-        num_lines += 1;
-        // 単語数 は、空白文字で区切られた文字列の数
-        num_words += line.split_whitespace().count();
-        num_chars += line.chars().count();
-        line.clear();
     }
+    regular_total.to_string().len().max(minimum_width)
+}
+
+/**
+ * 標準入力が通常のファイルの場合は、そのサイズを返す
+ */
+#[cfg(unix)]
+fn stdin_len() -> Option<u64> {
+    use std::os::fd::AsFd;
+    let fd = io::stdin().as_fd().try_clone_to_owned().ok()?;
+    let meta = fs::File::from(fd).metadata().ok()?;
+    meta.is_file().then_some(meta.len())
+}
+
+#[cfg(not(unix))]
+fn stdin_len() -> Option<u64> {
+    None
+}
 
-    Ok(FileInfo {
-        num_lines,
-        num_words,
-        num_bytes,
-        num_chars,
-    })
+/**
+ * ファイルの要素をカウント
+ *
+ * 引数 impl BufRead は、BufRead トレイトを実装している型を受け取ることを示す
+ *
+ * 行単位ではなく大きなブロック単位でバイト列のまま数えるので、不正なUTF-8を含む入力も数えられる
+ */
+pub fn count(file: impl BufRead) -> MyResult<FileInfo> {
+    Ok(counter::count_reader(file, Scan::ALL)?.into_info())
 }
 
 /**
  * テスト用モジュール
- * 
+ *
  * cfg 属性は、コンパイラにコードをコンパイルするかどうかを指示する
  *  
  */
 #[cfg(test)]
 mod tests {
-    use super::{count, format_field, FileInfo};
-    use std::io::Cursor;
+    use super::{count, format_field, run_with, Config, FileInfo, Format, LineStats, Total};
+    use std::io::{self, Cursor};
 
     #[test]
     fn test_count() {
         let text: &str = "I don't want the world. I just want your half.\r\n";
-        let info  = count(Cursor::new(text));
+        let info = count(Cursor::new(text));
         assert!(info.is_ok());
 
         let expected = FileInfo {
@@ -214,14 +665,90 @@ mod tests {
             num_words: 10,
             num_bytes: 48,
             num_chars: 48,
+            num_graphemes: 47,
+            max_line_length: 46,
+            width: 46,
+            stats: LineStats::default(),
         };
         assert_eq!(info.unwrap(), expected);
     }
 
     #[test]
     fn test_format_filed() {
-        assert_eq!(format_field(1, false), "");
-        assert_eq!(format_field(3, true), "       3");
-        assert_eq!(format_field(10, true), "      10");
+        assert_eq!(format_field(3, 1), "3");
+        assert_eq!(format_field(3, 7), "      3");
+        assert_eq!(format_field(1000, 3), "1000");
+    }
+
+    #[test]
+    fn test_run_with() {
+        let config = Config::new(vec!["-".to_string(), "no-such-file".to_string()]);
+        let mut stdin = Cursor::new("one two\nthree\n");
+        let mut out = vec![];
+        let mut err = vec![];
+        assert!(run_with(config, &mut stdin, &mut out, &mut err).is_err());
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "      2       3      14 -\n      2       3      14 total\n"
+        );
+        assert!(String::from_utf8(err)
+            .unwrap()
+            .starts_with("wcr: no-such-file: "));
+
+        let config = Config::new(vec!["tests/inputs/fox.txt".to_string()]).chars(true);
+        let mut out = vec![];
+        assert!(run_with(config, &mut io::empty(), &mut out, &mut io::sink()).is_ok());
+        assert_eq!(String::from_utf8(out).unwrap(), "48 tests/inputs/fox.txt\n");
+
+        // ファイルを指定しない場合は名前を出力しない
+        let mut stdin = Cursor::new("one two\n");
+        let mut out = vec![];
+        assert!(run_with(Config::new(vec![]), &mut stdin, &mut out, &mut io::sink()).is_ok());
+        assert_eq!(String::from_utf8(out).unwrap(), "      1       2       8\n");
+    }
+
+    #[test]
+    fn test_run_with_total() {
+        let files = vec![
+            "tests/inputs/fox.txt".to_string(),
+            "tests/inputs/atlamal.txt".to_string(),
+        ];
+        let run = |total: Total| {
+            let config = Config::new(files.clone()).lines(true).total(total);
+            let mut out = vec![];
+            run_with(config, &mut io::empty(), &mut out, &mut io::sink()).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let per_file = "  1 tests/inputs/fox.txt\n  4 tests/inputs/atlamal.txt\n";
+        assert_eq!(run(Total::Auto), format!("{per_file}  5 total\n"));
+        assert_eq!(run(Total::Never), per_file);
+        assert_eq!(run(Total::Only), "  5\n");
+
+        let config = Config::new(vec!["tests/inputs/fox.txt".to_string()]).total(Total::Always);
+        let mut out = vec![];
+        run_with(config, &mut io::empty(), &mut out, &mut io::sink()).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            " 1  9 48 tests/inputs/fox.txt\n 1  9 48 total\n"
+        );
+    }
+
+    #[test]
+    fn test_run_with_format() {
+        let files = vec!["-".to_string(), "no-such-file".to_string()];
+        let config = Config::new(files).format(Format::Csv).lines(true);
+        let mut stdin = Cursor::new("one two\nthree\n");
+        let mut out = vec![];
+        assert!(run_with(config, &mut stdin, &mut out, &mut io::sink()).is_err());
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            lines[0],
+            "type,name,lines,words,bytes,chars,max_line_length,width,graphemes,error"
+        );
+        assert_eq!(lines[1], "file,-,2,3,14,14,7,12,14,");
+        assert!(lines[2].starts_with("file,no-such-file,,,,,,,,"));
+        assert_eq!(lines[3], "total,,2,3,14,14,7,12,14,");
+        assert_eq!(lines.len(), 4);
     }
-}
\ No newline at end of file
+}
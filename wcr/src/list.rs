@@ -0,0 +1,157 @@
+use rcommon::{MyResult, Reporter};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+
+/**
+ * 数えるファイルの一覧の読み込み元
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FileList {
+    pub path: String,  // "-" の場合は標準入力
+    pub delimiter: u8, // --files0-from は b'\0'、--files-from は b'\n'
+}
+
+impl FileList {
+    /**
+     * 一覧を読み込んで、ファイル名のリストを返す
+     *
+     * 空のファイル名や、一覧を標準入力から読む場合の "-" は、そのエントリだけエラーとして報告して飛ばす。
+     * 一覧そのものが読めない場合は Err を返す
+     */
+    pub fn read(&self, stdin: &mut dyn BufRead, reporter: &mut Reporter) -> MyResult<Vec<PathBuf>> {
+        let from_stdin = self.path == "-";
+        let mut reader: Box<dyn BufRead + '_> = if from_stdin {
+            Box::new(stdin)
+        } else {
+            let file = File::open(&self.path)
+                .map_err(|e| format!("cannot open '{}' for reading: {}", self.path, e))?;
+            Box::new(BufReader::new(file))
+        };
+
+        let mut files = vec![];
+        let mut entry = vec![];
+        let mut number = 0;
+        loop {
+            entry.clear();
+            let read = reader
+                .read_until(self.delimiter, &mut entry)
+                .map_err(|e| format!("{}: read error: {}", self.path, e))?;
+            if read == 0 {
+                break;
+            }
+            number += 1;
+            if entry.last() == Some(&self.delimiter) {
+                entry.pop();
+            }
+
+            // 最後の区切り文字のあとは空のファイル名とはみなさない (read_until が 0 を返す)
+            if entry.is_empty() {
+                let location = format!("{}:{}", self.path, number);
+                reporter.error(&location, "invalid zero-length file name");
+            } else if from_stdin && entry == b"-" {
+                reporter.error(
+                    "-",
+                    "when reading file names from standard input, no file name of '-' allowed",
+                );
+            } else {
+                files.push(to_path(&entry));
+            }
+        }
+        Ok(files)
+    }
+}
+
+/**
+ * 一覧のエントリをパスにする
+ *
+ * Unix ではファイル名は任意のバイト列なので、UTF-8 でなくてもそのまま使う
+ */
+#[cfg(unix)]
+fn to_path(entry: &[u8]) -> PathBuf {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(OsStr::from_bytes(entry))
+}
+
+#[cfg(not(unix))]
+fn to_path(entry: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(entry).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileList;
+    use rcommon::Reporter;
+    use std::io::{self, Cursor};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_read() {
+        let list = FileList {
+            path: "-".to_string(),
+            delimiter: b'\0',
+        };
+        let mut stdin = Cursor::new(&b"a.txt\0with\nnewline\0\0-\0last"[..]);
+        let mut err = vec![];
+        let mut reporter = Reporter::new("wcr", &mut err);
+        let files = list.read(&mut stdin, &mut reporter).unwrap();
+        let expected: Vec<PathBuf> = ["a.txt", "with\nnewline", "last"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(files, expected);
+        assert!(reporter.failed());
+        assert_eq!(
+            String::from_utf8(err).unwrap(),
+            "wcr: -:3: invalid zero-length file name\n\
+             wcr: -: when reading file names from standard input, no file name of '-' allowed\n"
+        );
+    }
+
+    #[test]
+    fn test_read_lines() {
+        let list = FileList {
+            path: "-".to_string(),
+            delimiter: b'\n',
+        };
+        let mut stdin = Cursor::new("a.txt\nb.txt\n");
+        let mut err = io::sink();
+        let mut reporter = Reporter::new("wcr", &mut err);
+        let files = list.read(&mut stdin, &mut reporter).unwrap();
+        assert_eq!(files, [PathBuf::from("a.txt"), PathBuf::from("b.txt")]);
+        assert!(!reporter.failed());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_read_non_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        // UTF-8 でないファイル名も、置き換えずにそのまま返す
+        let list = FileList {
+            path: "-".to_string(),
+            delimiter: b'\0',
+        };
+        let mut stdin = Cursor::new(&b"caf\xe9.txt\0"[..]);
+        let mut err = io::sink();
+        let mut reporter = Reporter::new("wcr", &mut err);
+        let files = list.read(&mut stdin, &mut reporter).unwrap();
+        assert_eq!(files, [PathBuf::from(OsStr::from_bytes(b"caf\xe9.txt"))]);
+    }
+
+    #[test]
+    fn test_read_missing() {
+        let list = FileList {
+            path: "no-such-list".to_string(),
+            delimiter: b'\n',
+        };
+        let mut err = io::sink();
+        let mut reporter = Reporter::new("wcr", &mut err);
+        let e = list.read(&mut io::empty(), &mut reporter).unwrap_err();
+        assert!(e
+            .to_string()
+            .starts_with("cannot open 'no-such-list' for reading: "));
+    }
+}
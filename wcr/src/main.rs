@@ -1,8 +1,3 @@
 fn main() {
-    if let Err(e) = wcr::get_args().and_then(wcr::run) {
-        eprintln!("{}", e);
-        std::process::exit(1);
-    }  
+    rcommon::exit_on_error("wcr", wcr::get_args().and_then(wcr::run));
 }
-
-
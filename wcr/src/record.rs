@@ -0,0 +1,127 @@
+use crate::FileInfo;
+use clap::ValueEnum;
+use rcommon::MyResult;
+use serde::Serialize;
+use std::io::Write;
+
+/**
+ * 出力形式
+ */
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    Text, // wc と同じ桁揃えの表
+    Json, // 1行に1つの JSON オブジェクト (JSON Lines)
+    Csv,
+    Tsv,
+}
+
+/**
+ * 機械で読むための出力の1レコード
+ *
+ * 項目名と並び順は変えないこと (CSV/TSV のヘッダーにもなる)。項目を増やす場合は error の前に加える。
+ * ファイルのレコードとの区別がつくように、合計のレコードは type を "total" にして name を空にする
+ */
+#[derive(Debug, Serialize)]
+pub(crate) struct Record<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    name: Option<&'a str>,
+    lines: Option<usize>,
+    words: Option<usize>,
+    bytes: Option<usize>,
+    chars: Option<usize>,
+    max_line_length: Option<usize>,
+    width: Option<usize>,
+    graphemes: Option<usize>,
+    error: Option<String>,
+}
+
+impl<'a> Record<'a> {
+    /**
+     * ファイルごとのレコード (標準入力は "-")
+     *
+     * 数えられなかった場合は、数の代わりに error にメッセージを入れる
+     */
+    pub fn file(name: &'a str, info: Result<&FileInfo, String>) -> Record<'a> {
+        match info {
+            Ok(info) => Record::counts("file", Some(name), info),
+            Err(error) => Record {
+                kind: "file",
+                name: Some(name),
+                lines: None,
+                words: None,
+                bytes: None,
+                chars: None,
+                max_line_length: None,
+                width: None,
+                graphemes: None,
+                error: Some(error),
+            },
+        }
+    }
+
+    pub fn total(info: &FileInfo) -> Record<'a> {
+        Record::counts("total", None, info)
+    }
+
+    fn counts(kind: &'static str, name: Option<&'a str>, info: &FileInfo) -> Record<'a> {
+        Record {
+            kind,
+            name,
+            lines: Some(info.num_lines),
+            words: Some(info.num_words),
+            bytes: Some(info.num_bytes),
+            chars: Some(info.num_chars),
+            max_line_length: Some(info.max_line_length),
+            width: Some(info.width),
+            graphemes: Some(info.num_graphemes),
+            error: None,
+        }
+    }
+}
+
+/**
+ * レコードを指定した形式で書き出す
+ */
+pub(crate) enum RecordWriter<'w> {
+    Json(&'w mut dyn Write),
+    Csv(Box<csv::Writer<&'w mut dyn Write>>),
+}
+
+impl<'w> RecordWriter<'w> {
+    /**
+     * Text はレコードを使わずに表として出力するので、ここでは扱わない
+     */
+    pub fn new(format: Format, out: &'w mut dyn Write) -> RecordWriter<'w> {
+        let delimiter = match format {
+            Format::Text => unreachable!("text format has no records"),
+            Format::Json => return RecordWriter::Json(out),
+            Format::Csv => b',',
+            Format::Tsv => b'\t',
+        };
+        RecordWriter::Csv(Box::new(
+            csv::WriterBuilder::new()
+                .delimiter(delimiter)
+                .from_writer(out),
+        ))
+    }
+
+    pub fn write(&mut self, record: &Record) -> MyResult<()> {
+        match self {
+            RecordWriter::Json(out) => {
+                serde_json::to_writer(&mut *out, record)?;
+                writeln!(out)?;
+            }
+            RecordWriter::Csv(writer) => writer.serialize(record)?,
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> MyResult<()> {
+        match self {
+            RecordWriter::Json(out) => out.flush()?,
+            RecordWriter::Csv(mut writer) => writer.flush()?,
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+// ヒストグラムの棒の最大の長さ
+const BAR_WIDTH: usize = 40;
+
+/**
+ * 行ごとの統計 (--stats)
+ *
+ * 行の長さは改行 (CRLF の場合は CR も) を除いた文字数。
+ * 中央値を求めるために全ての行の長さを覚えておく代わりに、長さごとの行数を持つ
+ */
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct LineStats {
+    lengths: BTreeMap<usize, usize>, // 行の長さ → その長さの行数
+    blank_lines: usize,              // 空白文字だけの行 (空行を含む)
+    trailing_whitespace: usize,      // 行末が空白文字の行
+    lf_lines: usize,
+    crlf_lines: usize,
+    missing_final_newline: usize, // 改行で終わらないファイルの数
+}
+
+impl LineStats {
+    /**
+     * 1行を加える
+     */
+    pub(crate) fn add_line(&mut self, len: usize, blank: bool, trailing: bool) {
+        *self.lengths.entry(len).or_default() += 1;
+        self.blank_lines += blank as usize;
+        self.trailing_whitespace += trailing as usize;
+    }
+
+    pub(crate) fn add_ending(&mut self, crlf: bool) {
+        if crlf {
+            self.crlf_lines += 1;
+        } else {
+            self.lf_lines += 1;
+        }
+    }
+
+    pub(crate) fn set_missing_final_newline(&mut self) {
+        self.missing_final_newline = 1;
+    }
+
+    /**
+     * 他のファイルの統計を合わせる
+     */
+    pub(crate) fn merge(&mut self, other: &LineStats) {
+        for (&len, &count) in &other.lengths {
+            *self.lengths.entry(len).or_default() += count;
+        }
+        self.blank_lines += other.blank_lines;
+        self.trailing_whitespace += other.trailing_whitespace;
+        self.lf_lines += other.lf_lines;
+        self.crlf_lines += other.crlf_lines;
+        self.missing_final_newline += other.missing_final_newline;
+    }
+
+    fn lines(&self) -> usize {
+        self.lengths.values().sum()
+    }
+
+    fn min(&self) -> Option<usize> {
+        self.lengths.keys().next().copied()
+    }
+
+    fn max(&self) -> Option<usize> {
+        self.lengths.keys().next_back().copied()
+    }
+
+    fn mean(&self) -> Option<f64> {
+        let lines = self.lines();
+        let sum: usize = self.lengths.iter().map(|(len, count)| len * count).sum();
+        (lines > 0).then(|| sum as f64 / lines as f64)
+    }
+
+    /**
+     * 行数が偶数の場合は、真ん中の2行の平均
+     */
+    fn median(&self) -> Option<f64> {
+        let lines = self.lines();
+        if lines == 0 {
+            return None;
+        }
+        let lower = self.nth((lines - 1) / 2);
+        let upper = self.nth(lines / 2);
+        Some((lower + upper) as f64 / 2.0)
+    }
+
+    /**
+     * 短い順に並べたときの n 番目 (0 から) の行の長さ
+     */
+    fn nth(&self, n: usize) -> usize {
+        let mut seen = 0;
+        for (&len, &count) in &self.lengths {
+            seen += count;
+            if n < seen {
+                return len;
+            }
+        }
+        unreachable!("n is less than the number of lines")
+    }
+
+    /**
+     * 行の長さを 0, 1, 2-3, 4-7, ... の区間に分けた行数
+     */
+    fn histogram(&self) -> Vec<(usize, usize, usize)> {
+        let mut buckets: Vec<(usize, usize, usize)> = vec![];
+        for (&len, &count) in &self.lengths {
+            let (low, high) = match len {
+                0 => (0, 0),
+                len => {
+                    let low = 1 << len.ilog2();
+                    (low, low * 2 - 1)
+                }
+            };
+            match buckets.last_mut() {
+                Some(last) if last.0 == low => last.2 += count,
+                _ => buckets.push((low, high, count)),
+            }
+        }
+        buckets
+    }
+
+    /**
+     * 統計を字下げして出力する
+     *
+     * files が1より大きい場合は合計として、改行で終わらないファイルの数を出力する
+     */
+    pub(crate) fn write_report(&self, out: &mut dyn Write, files: usize) -> io::Result<()> {
+        match (self.min(), self.max(), self.mean(), self.median()) {
+            (Some(min), Some(max), Some(mean), Some(median)) => writeln!(
+                out,
+                "    line length: min {}, max {}, mean {:.1}, median {:.1}",
+                min, max, mean, median
+            )?,
+            _ => writeln!(out, "    line length: no lines")?,
+        }
+        writeln!(out, "    blank lines: {}", self.blank_lines)?;
+        writeln!(
+            out,
+            "    trailing whitespace lines: {}",
+            self.trailing_whitespace
+        )?;
+        writeln!(
+            out,
+            "    line endings: {} LF, {} CRLF",
+            self.lf_lines, self.crlf_lines
+        )?;
+        if files > 1 {
+            writeln!(
+                out,
+                "    missing final newline: {} of {} files",
+                self.missing_final_newline, files
+            )?;
+        } else {
+            let missing = if self.missing_final_newline > 0 {
+                "yes"
+            } else {
+                "no"
+            };
+            writeln!(out, "    missing final newline: {}", missing)?;
+        }
+
+        let histogram = self.histogram();
+        let most = histogram.iter().map(|b| b.2).max().unwrap_or(0);
+        for (low, high, count) in histogram {
+            let range = if low == high {
+                low.to_string()
+            } else {
+                format!("{}-{}", low, high)
+            };
+            // 1行でもあれば棒を1文字は出す
+            let bar = (count * BAR_WIDTH).div_ceil(most);
+            writeln!(out, "    {:>11} {:>7} {}", range, count, "#".repeat(bar))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LineStats;
+
+    fn line_stats(lengths: &[usize]) -> LineStats {
+        let mut stats = LineStats::default();
+        for &len in lengths {
+            stats.add_line(len, len == 0, false);
+            stats.add_ending(false);
+        }
+        stats
+    }
+
+    #[test]
+    fn test_summary() {
+        let stats = line_stats(&[3, 0, 10, 4]);
+        assert_eq!(stats.lines(), 4);
+        assert_eq!(stats.min(), Some(0));
+        assert_eq!(stats.max(), Some(10));
+        assert_eq!(stats.mean(), Some(4.25));
+        assert_eq!(stats.median(), Some(3.5));
+        assert_eq!(stats.blank_lines, 1);
+
+        assert_eq!(line_stats(&[5, 1, 5]).median(), Some(5.0));
+        assert_eq!(LineStats::default().median(), None);
+    }
+
+    #[test]
+    fn test_histogram() {
+        let stats = line_stats(&[0, 1, 2, 3, 3, 9, 15, 16]);
+        assert_eq!(
+            stats.histogram(),
+            [(0, 0, 1), (1, 1, 1), (2, 3, 3), (8, 15, 2), (16, 31, 1)]
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut total = line_stats(&[1, 2]);
+        let mut other = line_stats(&[2]);
+        other.set_missing_final_newline();
+        total.merge(&other);
+        assert_eq!(total.lines(), 3);
+        assert_eq!(total.median(), Some(2.0));
+        assert_eq!(total.lf_lines, 3);
+        assert_eq!(total.missing_final_newline, 1);
+    }
+
+    #[test]
+    fn test_write_report() {
+        let mut out = vec![];
+        line_stats(&[2, 3]).write_report(&mut out, 1).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "    line length: min 2, max 3, mean 2.5, median 2.5\n\
+             \x20   blank lines: 0\n\
+             \x20   trailing whitespace lines: 0\n\
+             \x20   line endings: 2 LF, 0 CRLF\n\
+             \x20   missing final newline: no\n\
+             \x20           2-3       2 ########################################\n"
+        );
+    }
+}
@@ -9,6 +9,7 @@ const PRG: &str = "wcr";
 const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const ATLAMAL: &str = "tests/inputs/atlamal.txt";
+const WIDE: &str = "tests/inputs/wide.txt";
 
 // --------------------------------------------------
 fn gen_bad_file() -> String {
@@ -27,15 +28,13 @@ fn gen_bad_file() -> String {
 
 // --------------------------------------------------
 #[test]
-fn dies_chars_and_bytes() -> Result<()> {
-    Command::cargo_bin(PRG)?
-        .args(["-m", "-c"])
-        .assert()
-        .failure()
-        .stderr(predicate::str::contains(
-            "'--chars' cannot be used with '--bytes'",
-        ));
-    Ok(())
+fn chars_and_bytes() -> Result<()> {
+    // GNU wc と同じく、文字数とバイト数の両方をこの順に出力する
+    run_str(&["-lwmc", ATLAMAL], "  4  29 159 177 tests/inputs/atlamal.txt\n")?;
+    run_str(
+        &["-cm", FOX, ATLAMAL],
+        " 48  48 tests/inputs/fox.txt\n159 177 tests/inputs/atlamal.txt\n207 225 total\n",
+    )
 }
 
 // --------------------------------------------------
@@ -50,6 +49,16 @@ fn run(args: &[&str], expected_file: &str) -> Result<()> {
     Ok(())
 }
 
+// --------------------------------------------------
+fn run_str(args: &[&str], expected: &str) -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .assert()
+        .success()
+        .stdout(expected.to_string());
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn skips_bad_file() -> Result<()> {
@@ -58,11 +67,24 @@ fn skips_bad_file() -> Result<()> {
     Command::cargo_bin(PRG)?
         .arg(bad)
         .assert()
-        .success()
+        .failure()
         .stderr(predicate::str::is_match(expected)?);
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn file_named_man() -> Result<()> {
+    // "man" はサブコマンドではなくファイル名として扱う
+    Command::cargo_bin(PRG)?
+        .current_dir("tests/inputs")
+        .arg("man")
+        .assert()
+        .success()
+        .stdout(" 1  5 24 man\n");
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn empty() -> Result<()> {
@@ -218,3 +240,318 @@ fn test_all_words_lines() -> Result<()> {
 fn test_all_bytes_lines() -> Result<()> {
     run(&["-cl", EMPTY, FOX, ATLAMAL], "tests/expected/all.cl.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn test_all_jobs() -> Result<()> {
+    run(
+        &["--jobs", "3", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.out",
+    )?;
+    run(
+        &["-j", "0", "-lwm", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.lwm.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn invalid_utf8_stdin() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .arg("-lwm")
+        .write_stdin(&b"caf\xe9 ok\n\xff"[..])
+        .assert()
+        .success()
+        .stdout("      1       3       7\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_max_line_length() -> Result<()> {
+    run(
+        &["-L", EMPTY, FOX, ATLAMAL, WIDE],
+        "tests/expected/all.L.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn wide_width() -> Result<()> {
+    run(
+        &["-lL", "--width", WIDE],
+        "tests/expected/wide.txt.lL.width.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_json() -> Result<()> {
+    run(
+        &["--format", "json", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.json.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_csv() -> Result<()> {
+    run(
+        &["--format", "csv", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.csv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_tsv() -> Result<()> {
+    // 項目を選んでも全ての数を出力する
+    run(
+        &["--format", "tsv", "-l", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.tsv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn json_stdin_and_bad_file() -> Result<()> {
+    let bad = gen_bad_file();
+    let output = Command::cargo_bin(PRG)?
+        .args(["--format", "json", "-", &bad])
+        .write_stdin("one two\n")
+        .output()
+        .expect("fail");
+    assert!(!output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with(r#"{"type":"file","name":"-","lines":1,"words":2,"bytes":8,"#));
+    assert!(lines[1].starts_with(&format!(r#"{{"type":"file","name":"{bad}","lines":null,"#)));
+    assert!(lines[1].contains("(os error 2)"));
+    assert!(lines[2].starts_with(r#"{"type":"total","name":null,"lines":1,"words":2,"bytes":8,"#));
+
+    let stderr = String::from_utf8(output.stderr).expect("invalid UTF-8");
+    assert!(stderr.starts_with(&format!("wcr: {bad}: ")));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_files_from() -> Result<()> {
+    run(
+        &["--files-from", "tests/inputs/list.txt"],
+        "tests/expected/all.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_files0_from_stdin() -> Result<()> {
+    // 一覧を標準入力から読む場合は桁を揃えない
+    let expected = fs::read_to_string("tests/expected/all.files0.out")?;
+    let list = format!("{EMPTY}\0{FOX}\0{ATLAMAL}\0");
+    Command::cargo_bin(PRG)?
+        .arg("--files0-from=-")
+        .write_stdin(list)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from_bad_entries() -> Result<()> {
+    let bad = gen_bad_file();
+    let list = format!("{FOX}\0\0{bad}\0-\0");
+    Command::cargo_bin(PRG)?
+        .arg("--files0-from=-")
+        .write_stdin(list)
+        .assert()
+        .failure()
+        .stdout(format!("1 9 48 {FOX}\n1 9 48 total\n"))
+        .stderr(predicate::str::contains(
+            "-:2: invalid zero-length file name",
+        ))
+        .stderr(predicate::str::contains(format!("{bad}: ")))
+        .stderr(predicate::str::contains("no file name of '-' allowed"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn files0_from_non_utf8_name() -> Result<()> {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    // UTF-8 でない名前のファイルも開ける (表示するときだけ置き換える)
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join(OsStr::from_bytes(b"caf\xe9.txt"));
+    fs::copy(FOX, &path)?;
+    let missing = dir.path().join(OsStr::from_bytes(b"\xff.txt"));
+    let mut list = path.as_os_str().as_bytes().to_vec();
+    list.push(b'\0');
+    list.extend(missing.as_os_str().as_bytes());
+    list.push(b'\0');
+
+    Command::cargo_bin(PRG)?
+        .arg("--files0-from=-")
+        .write_stdin(list)
+        .assert()
+        .failure()
+        .stdout(format!("1 9 48 {}\n1 9 48 total\n", path.display()))
+        .stderr(predicate::str::starts_with(format!(
+            "wcr: {}: ",
+            missing.display()
+        )));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_files_from_missing_list() -> Result<()> {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["--files-from", &bad])
+        .assert()
+        .failure()
+        .stderr(predicate::str::starts_with(format!(
+            "wcr: cannot open '{bad}' for reading: "
+        )));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_files_from_and_files() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--files0-from", "-", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_named_dash() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-l", "-"])
+        .write_stdin("a\nb\n")
+        .assert()
+        .success()
+        .stdout("2 -\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_total_only() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--total=only", EMPTY, FOX, ATLAMAL])
+        .assert()
+        .success()
+        .stdout("  5  38 225\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn fox_total_always() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--total", "always", "-c", FOX])
+        .assert()
+        .success()
+        .stdout(format!("48 {FOX}\n48 total\n"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_total_never() -> Result<()> {
+    let expected = fs::read_to_string("tests/expected/all.out")?;
+    let expected = expected.lines().take(3).collect::<Vec<_>>().join("\n");
+    run_str(
+        &["--total=never", EMPTY, FOX, ATLAMAL],
+        &format!("{expected}\n"),
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn directory_and_bad_file_keep_total() -> Result<()> {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", &bad, FOX])
+        .assert()
+        .failure()
+        .stdout(format!(
+            "      0       0       0 tests/inputs\n      1       9      48 {FOX}\n      1       9      48 total\n"
+        ))
+        .stderr(predicate::str::contains("wcr: tests/inputs: Is a directory"))
+        .stderr(predicate::str::contains(format!("wcr: {bad}: ")));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn wide_unicode_words_graphemes() -> Result<()> {
+    run(
+        &["--words=unicode", "--graphemes", "-m", WIDE],
+        "tests/expected/wide.txt.unicode.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn unicode_words_stdin() -> Result<()> {
+    // 空白で区切ると1語になる日本語の文も、UAX #29 では単語に分かれる
+    Command::cargo_bin(PRG)?
+        .args(["-l", "--words=unicode"])
+        .write_stdin("今日は晴れ。\n")
+        .assert()
+        .success()
+        .stdout("      1       5\n");
+    Command::cargo_bin(PRG)?
+        .args(["-l", "--words"])
+        .write_stdin("今日は晴れ。\n")
+        .assert()
+        .success()
+        .stdout("      1       1\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_word_mode() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .arg("--words=foo")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value 'foo'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn stats_report() -> Result<()> {
+    run(
+        &["--stats", "tests/inputs/stats.txt", FOX],
+        "tests/expected/stats.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_stats_and_format() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--stats", "--format", "json"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "'--stats' cannot be used with '--format <FORMAT>'",
+        ));
+    Ok(())
+}